@@ -1,14 +1,28 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
 use bp::dbc::Method;
 use rand::{Rng, SeedableRng};
 use rgbinvoice::{RgbInvoice, RgbInvoiceBuilder};
-use rgbstd::containers::{Contract, Transfer, ValidContract};
+use rgbstd::containers::{Consignment, Contract, Transfer, ValidContract, ValidTransfer};
 use rgbstd::persistence::{IndexProvider, StashProvider, StateProvider, Stock};
+use rgbstd::validation::ResolveWitness;
+use rgbstd::stl::{ContractTerms, RicardianContract};
 use rgbstd::OutputSeal;
 
 use crate::types::*;
 
 use crate::detail;
 use crate::detail::PartialFascia;
+use crate::backup;
+use crate::consignment;
+use crate::error::{
+    AmountError, BackupError, ConsignmentError, InvoiceError, IssueError, NetworkError, RbfError,
+    StockFailure, TransferError, ValidationError,
+};
+use crate::issue::{self, IssueRequest};
+use crate::store::{self, LockedFsStore, SqliteStock};
 
 
 pub fn rgb_issue(
@@ -18,33 +32,182 @@ pub fn rgb_issue(
     details: Option<&str>,
     precision: u8,
     allocations: impl IntoIterator<Item = (String, u64)>,
-    is_testnet: bool,
+    network: Network,
 ) -> ValidContract {
-    detail::rgb_issue(issuer, ticker, name, details, precision, allocations, is_testnet)
+    let terms = ContractTerms {
+        text: RicardianContract::default(),
+        media: None,
+    };
+    detail::rgb_issue(issuer, ticker, name, details, precision, terms, allocations, network)
+}
+
+/// Issues a contract with allocations given as amounts, e.g. `1.5 TEST`,
+/// rescaled to `precision`.
+pub fn rgb_issue_amounts(
+    issuer: &str,
+    ticker: &str,
+    name: &str,
+    details: Option<&str>,
+    precision: u8,
+    allocations: impl IntoIterator<Item = (String, RgbAmount)>,
+    network: Network,
+) -> Result<ValidContract, AmountError> {
+    let allocations = allocations
+        .into_iter()
+        .map(|(seal, amount)| Ok((seal, amount.to_atomic(precision, ticker)?)))
+        .collect::<Result<Vec<_>, AmountError>>()?;
+    Ok(rgb_issue(issuer, ticker, name, details, precision, allocations, network))
+}
+
+/// Issues a contract from a validated request, see `IssueRequest::validate`.
+pub fn rgb_issue_from_request(request: &IssueRequest) -> Result<ValidContract, IssueError> {
+    request.issue()
 }
 
+/// Issues a contract committing to the Ricardian contract `terms` and to an
+/// optional media file, given with its MIME type, e.g. `application/pdf`.
+#[allow(clippy::too_many_arguments)]
+pub fn rgb_issue_with_terms(
+    issuer: &str,
+    ticker: &str,
+    name: &str,
+    details: Option<&str>,
+    precision: u8,
+    terms: &str,
+    media: Option<(&str, &[u8])>,
+    allocations: impl IntoIterator<Item = (String, u64)>,
+    network: Network,
+) -> Result<ValidContract, IssueError> {
+    let terms = issue::contract_terms(terms, media)?;
+    Ok(detail::rgb_issue(issuer, ticker, name, details, precision, terms, allocations, network))
+}
+
+pub fn rgb_contract_terms<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+) -> Option<Terms> {
+    detail::rgb_contract_terms(stock, contract_id.to_raw()).map(Terms::from)
+}
+
+/// Checks that `file` is the media attached to the terms of the contract.
+pub fn rgb_verify_contract_media<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    file: &[u8],
+) -> bool {
+    rgb_contract_terms(stock, contract_id)
+        .and_then(|terms| terms.media)
+        .is_some_and(|media| media.verify(file))
+}
+
+/// Refuses contracts which weren't issued for `network`.
 pub fn rgb_balance<S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &Stock<S, H, P>,
     contract_id: ContractId,
     utxos: &[Outpoint],
-) -> u64 {
+    network: Network,
+) -> Result<u64, NetworkError> {
+    detail::check_contract_network(stock, contract_id.to_raw(), network)?;
+
     let utxos: Vec<RawOutpoint> =
         utxos.iter().copied().map(ToRaw::to_raw).collect();
 
-    detail::rgb_balance(stock, contract_id.to_raw(), &utxos)
+    Ok(detail::rgb_balance(stock, contract_id.to_raw(), &utxos))
+}
+
+/// Balance with the precision and ticker of the contract, `None` if it's
+/// unknown, doesn't implement RGB20 or wasn't issued for `network`.
+pub fn rgb_balance_amount<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    utxos: &[Outpoint],
+    network: Network,
+) -> Option<RgbAmount> {
+    let (precision, ticker) = detail::rgb_precision_and_ticker(stock, contract_id.to_raw())?;
+    let balance = rgb_balance(stock, contract_id, utxos, network).ok()?;
+    let amount = RgbAmount::new(balance, precision).expect("contract precision is valid");
+    Some(amount.with_ticker(ticker))
 }
 
 pub fn filter_rgb_outpoints<S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &Stock<S, H, P>,
     utxos: &[Outpoint],
-) -> Vec<Outpoint> {
+) -> Result<Vec<Outpoint>, StockFailure> {
     let utxos: Vec<RawOutpoint> =
         utxos.iter().copied().map(ToRaw::to_raw).collect();
 
-    detail::filter_rgb_outpoints(stock, &utxos)
+    Ok(detail::filter_rgb_outpoints(stock, &utxos)?
         .into_iter()
-        .map(|o| Outpoint::from(o))
-        .collect()
+        .map(Outpoint::from)
+        .collect())
+}
+
+/// Lists everything allocated to each of `utxos` across all the contracts,
+/// outpoints without any RGB state map to an empty list.
+///
+/// Fails only if the stock can't be read.
+pub fn rgb_allocations<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    utxos: &[Outpoint],
+) -> Result<BTreeMap<Outpoint, Vec<Allocation>>, StockFailure> {
+    let mut allocations: BTreeMap<Outpoint, Vec<Allocation>> =
+        utxos.iter().map(|o| (*o, vec![])).collect();
+
+    let utxos: Vec<RawOutpoint> =
+        utxos.iter().copied().map(ToRaw::to_raw).collect();
+    for (outpoint, contract_id, opout, state) in detail::rgb_allocations(stock, &utxos)? {
+        allocations
+            .entry(outpoint.into())
+            .or_default()
+            .push(Allocation {
+                contract_id: contract_id.into(),
+                opout: opout.into(),
+                state,
+            });
+    }
+    allocations.values_mut().for_each(|a| a.sort());
+
+    Ok(allocations)
+}
+
+/// Returns `None` if the contract is unknown, doesn't implement RGB20 or
+/// wasn't issued for `network`, an error only if the stock can't be read.
+pub fn rgb_contract_info<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    network: Network,
+) -> Result<Option<ContractInfo>, StockFailure> {
+    detail::rgb_contract_info(stock, contract_id.to_raw(), network)
+}
+
+/// Lists the RGB20 contracts of `network` known to the stock.
+pub fn rgb_list_contracts<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    network: Network,
+) -> Result<Vec<ContractInfo>, StockFailure> {
+    let mut contracts = vec![];
+    for info in stock.contracts().map_err(|e| StockFailure(e.to_string()))? {
+        contracts.extend(detail::rgb_contract_info(stock, info.id, network)?);
+    }
+    Ok(contracts)
+}
+
+/// Lists the operations of a contract in the order they were applied, with
+/// the amount each of them moved to or from `owned_outpoints`.
+///
+/// Returns `None` if the contract is unknown, an error only if the stock
+/// can't be read. Operations whose witness is archived, e.g. replaced by RBF,
+/// are listed last with the outputs read from their transitions, since those
+/// are not part of the contract state, and with no net change.
+pub fn rgb_history<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    owned_outpoints: &[Outpoint],
+) -> Result<Option<Vec<HistoryEntry>>, StockFailure> {
+    let owned_outpoints: Vec<RawOutpoint> =
+        owned_outpoints.iter().copied().map(ToRaw::to_raw).collect();
+
+    detail::rgb_history(stock, contract_id.to_raw(), &owned_outpoints)
 }
 
 pub fn rgb_coin_select<S: StashProvider, H: StateProvider, P: IndexProvider>(
@@ -67,12 +230,18 @@ pub fn rgb_coin_select<S: StashProvider, H: StateProvider, P: IndexProvider>(
         .collect()
 }
 
+/// Refuses assignments of contracts which weren't issued for `network`.
 pub fn rgb_compose<S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &Stock<S, H, P>,
     prev_outputs: impl IntoIterator<Item = Outpoint>,
     rgb_assignments: RgbAssignments,
     change_seal: Option<Beneficiary>,
-) -> Vec<TransitionInfo> {
+    network: Network,
+) -> Result<Vec<TransitionInfo>, TransferError> {
+    for contract_id in rgb_assignments.contracts() {
+        detail::check_contract_network(stock, contract_id.to_raw(), network)?;
+    }
+
     let prev_outputs = prev_outputs
         .into_iter()
         .collect::<Vec<_>>();
@@ -118,12 +287,12 @@ pub fn rgb_compose<S: StashProvider, H: StateProvider, P: IndexProvider>(
         change_seal,
         &mut rng,
     )
-    .unwrap();
+    .map_err(|e| TransferError::Stock(e.to_string()))?;
 
-    transition_info_list
+    Ok(transition_info_list
         .into_iter()
         .map(TransitionInfo)
-        .collect()
+        .collect())
 }
 
 pub fn rgb_commit(
@@ -147,17 +316,21 @@ pub fn rgb_commit(
     (commitment.to_byte_array(), partial_fascia)
 }
 
+/// Refuses contracts which weren't issued for `network`.
 pub fn rgb_transfer<S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &Stock<S, H, P>,
     contract_id: ContractId,
     outputs: &[Outpoint],
     secret_seal: Option<[u8; 32]>,
-) -> Transfer {
+    network: Network,
+) -> Result<Transfer, TransferError> {
     use rgbstd::OutputSeal;
     use bp::seals::txout::CloseMethod;
 
+    detail::check_contract_network(stock, contract_id.to_raw(), network)?;
+
     let outputs = outputs
-        .into_iter()
+        .iter()
         .map(|o| {
             o
                 .to_raw()
@@ -169,6 +342,120 @@ pub fn rgb_transfer<S: StashProvider, H: StateProvider, P: IndexProvider>(
 
     let secret_seal = secret_seal.map(|s| XChain::with(rgbstd::Layer1::Bitcoin, SecretSeal::from(s)));
     detail::rgb_transfer(stock, contract_id.to_raw(), &outputs, secret_seal)
+        .map_err(TransferError::Stock)
+}
+
+/// Consignments for a list of `(contract_id, outputs, secret_seal)` sent by
+/// the same witness, keyed by contract.
+///
+/// Besides the requested contracts, every contract assigning state to one of
+/// the requested outputs or secret seals, e.g. moved there by a blank
+/// transition, is consigned, so the recipient can spend it. Contracts moved
+/// only to other outputs, like the sender's change, are not.
+#[allow(clippy::type_complexity)]
+pub fn rgb_transfer_many<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    transfers: &[(ContractId, Vec<Outpoint>, Option<[u8; 32]>)],
+    network: Network,
+) -> Result<BTreeMap<ContractId, Transfer>, TransferError> {
+    let requests = transfers
+        .iter()
+        .map(|(contract_id, outputs, secret_seal)| {
+            let outputs = outputs.iter().copied().map(ToRaw::to_raw).collect();
+            let secret_seal = secret_seal
+                .map(|s| XChain::with(rgbstd::Layer1::Bitcoin, SecretSeal::from(s)));
+            (contract_id.to_raw(), outputs, secret_seal)
+        })
+        .collect::<Vec<_>>();
+
+    let transfers = detail::rgb_transfer_many(stock, &requests, network)?;
+    Ok(transfers
+        .into_iter()
+        .map(|(contract_id, transfer)| (contract_id.into(), transfer))
+        .collect())
+}
+
+/// Checks that the contract, if known to the stock, was issued for `network`.
+pub fn rgb_check_contract<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    network: Network,
+) -> Result<(), NetworkError> {
+    detail::check_contract_network(stock, contract_id.to_raw(), network)
+}
+
+/// Validates a contract received from someone else, refusing contracts
+/// issued for another network.
+pub fn rgb_validate_contract(
+    contract: Contract,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidContract, ValidationError> {
+    detail::validate_consignment(contract, resolver, network)
+}
+
+/// Validates a transfer before accepting it, refusing transfers of contracts
+/// issued for another network.
+pub fn rgb_validate_transfer(
+    transfer: Transfer,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidTransfer, ValidationError> {
+    detail::validate_consignment(transfer, resolver, network)
+}
+
+/// Re-queries the ordering of all the witnesses known to the stock, e.g.
+/// after a reorg or a double spend, and reports the balances which changed.
+///
+/// Witnesses which fail to resolve are reported and keep their ordering, a
+/// failure of the stock itself is returned as an error.
+pub fn rgb_update_witnesses<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    resolver: impl ResolveWitness,
+) -> Result<WitnessUpdateReport, StockFailure> {
+    let (changes, failed) = detail::rgb_update_witnesses(stock, resolver)?;
+
+    let balance_changes = changes
+        .into_iter()
+        .map(|((contract_id, outpoint), (before, after))| BalanceChange {
+            contract_id: contract_id.into(),
+            outpoint: outpoint.into(),
+            before,
+            after,
+        })
+        .collect();
+    let failed = failed
+        .into_iter()
+        .map(|(witness_id, e)| (Txid::from(*witness_id.as_reduced_unsafe()), e))
+        .collect();
+
+    Ok(WitnessUpdateReport {
+        balance_changes,
+        failed,
+    })
+}
+
+/// Re-colors a tx replaced by RBF, e.g. after a fee bump.
+///
+/// `partial_fascia` must be the one `rgb_commit` returned for the replaced
+/// tx. The stock doesn't keep it, so callers which may bump the fee have to
+/// hold on to it until the tx is mined. The replacement must spend the same
+/// inputs in the same order and keep the commitment output as is. Its fascia
+/// is consumed into the stock, and the replaced witness is archived.
+pub fn rgb_rbf_recolor<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    partial_fascia: PartialFascia,
+    consensus_serialized_replaced_tx: &[u8],
+    consensus_serialized_replacement_tx: &[u8],
+) -> Result<(), RbfError> {
+    use bp::ConsensusDecode;
+
+    let replaced_tx = bp::Tx::consensus_deserialize(consensus_serialized_replaced_tx)
+        .map_err(|e| RbfError::Decode(e.to_string()))?;
+    let replacement_tx = bp::Tx::consensus_deserialize(consensus_serialized_replacement_tx)
+        .map_err(|e| RbfError::Decode(e.to_string()))?;
+
+    detail::rgb_rbf_recolor(stock, partial_fascia, &replaced_tx, &replacement_tx)
 }
 
 pub fn get_empty_stock() -> Stock {
@@ -181,6 +468,114 @@ pub fn get_empty_stock() -> Stock {
     stock
 }
 
+/// Creates a persistent stock in `path`, with the NIA kit imported.
+///
+/// Fails if there is already a stock there, or if it's in use by another
+/// process. Changes are saved as they are made, but the stash, the state and
+/// the index are separate files, so a crash in the middle of saving may leave
+/// them out of sync. Prefer `create_sqlite_stock` if that's a concern.
+pub fn create_stock(path: impl AsRef<Path>) -> io::Result<Stock> {
+    let provider = LockedFsStore::new(path.as_ref())?;
+    if provider.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("stock already exists at {}", path.as_ref().display()),
+        ));
+    }
+
+    let mut stock = get_empty_stock();
+    stock
+        .make_persistent(provider, true)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    stock.store().map_err(|e| io::Error::other(e.to_string()))?;
+
+    Ok(stock)
+}
+
+/// Opens a stock created by `create_stock`.
+///
+/// The stock is locked until it's dropped.
+pub fn open_stock(path: impl AsRef<Path>) -> io::Result<Stock> {
+    let provider = LockedFsStore::new(path.as_ref())?;
+    if !provider.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no stock at {}", path.as_ref().display()),
+        ));
+    }
+
+    Stock::load(provider, true)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Creates a stock in the SQLite database at `path`, with the NIA kit
+/// imported.
+///
+/// Unlike `create_stock`, changes are written incrementally, and each stock
+/// operation, e.g. consuming a fascia, is applied in a single database
+/// transaction. The database is locked until the stock is dropped.
+pub fn create_sqlite_stock(path: impl AsRef<Path>) -> io::Result<SqliteStock> {
+    use schemata::NonInflatableAsset;
+    use ifaces::IssuerWrapper;
+
+    let path = path.as_ref();
+    if path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("stock already exists at {}", path.display()),
+        ));
+    }
+
+    let mut stock = store::open_sqlite(path).map_err(io::Error::other)?;
+    stock
+        .import_kit(NonInflatableAsset::kit())
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    Ok(stock)
+}
+
+/// Opens a stock created by `create_sqlite_stock`.
+pub fn open_sqlite_stock(path: impl AsRef<Path>) -> io::Result<SqliteStock> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no stock at {}", path.display()),
+        ));
+    }
+
+    store::open_sqlite(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes a backup of the stock, from which `rgb_restore` can rebuild it.
+///
+/// The archive holds the imported kits, every contract with its valid
+/// history, and the secret seals of the issued invoices. It's encrypted if a
+/// passphrase is given.
+pub fn rgb_backup<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    writer: impl Write,
+    passphrase: Option<&str>,
+) -> Result<(), BackupError> {
+    backup::rgb_backup(stock, writer, passphrase)
+}
+
+/// Restores a backup written by `rgb_backup` into `stock`, e.g. a new one
+/// from `create_stock` or `create_sqlite_stock`.
+///
+/// Contracts are validated against the witnesses and their ordering as they
+/// were at backup time, use `rgb_update_witnesses` to refresh them. Witnesses
+/// stored without their tx, e.g. from `PartialFascia::complete_with_txid`,
+/// aren't in the backup and are resolved by `fallback`.
+pub fn rgb_restore<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    reader: impl Read,
+    passphrase: Option<&str>,
+    fallback: Option<&dyn ResolveWitness>,
+) -> Result<(), BackupError> {
+    backup::rgb_restore(stock, reader, passphrase, fallback)
+}
+
 pub fn rgb_export_contract<S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &Stock<S, H, P>,
     contract_id: ContractId,
@@ -188,6 +583,75 @@ pub fn rgb_export_contract<S: StashProvider, H: StateProvider, P: IndexProvider>
     stock.export_contract(contract_id.to_raw()).unwrap()
 }
 
+/// Writes a contract as a strict-encoded binary file.
+pub fn rgb_write_contract(contract: &Contract, writer: impl Write) -> Result<(), ConsignmentError> {
+    consignment::write(contract, writer)
+}
+
+/// Reads a contract written by `rgb_write_contract` and validates it.
+pub fn rgb_read_contract(
+    reader: impl Read,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidContract, ConsignmentError> {
+    consignment::read(reader, resolver, network)
+}
+
+/// ASCII-armors a contract, with its id, version and consignment id in the
+/// headers and a checksum of the data.
+pub fn rgb_armor_contract(contract: &Contract) -> String {
+    consignment::armor(contract)
+}
+
+/// Parses a contract armored by `rgb_armor_contract`, checking its checksum
+/// and headers, and validates it.
+pub fn rgb_unarmor_contract(
+    s: &str,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidContract, ConsignmentError> {
+    consignment::unarmor(s, resolver, network)
+}
+
+/// Writes a transfer as a strict-encoded binary file.
+pub fn rgb_write_transfer(transfer: &Transfer, writer: impl Write) -> Result<(), ConsignmentError> {
+    consignment::write(transfer, writer)
+}
+
+/// Reads a transfer written by `rgb_write_transfer` and validates it.
+pub fn rgb_read_transfer(
+    reader: impl Read,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidTransfer, ConsignmentError> {
+    consignment::read(reader, resolver, network)
+}
+
+/// ASCII-armors a transfer, with its contract id, version and consignment id
+/// in the headers and a checksum of the data.
+pub fn rgb_armor_transfer(transfer: &Transfer) -> String {
+    consignment::armor(transfer)
+}
+
+/// Parses a transfer armored by `rgb_armor_transfer`, checking its checksum
+/// and headers, and validates it.
+pub fn rgb_unarmor_transfer(
+    s: &str,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidTransfer, ConsignmentError> {
+    consignment::unarmor(s, resolver, network)
+}
+
+/// Standard file name of a contract or transfer, named after its consignment
+/// id with the `rgb` extension, or `rgba` if it's armored.
+pub fn rgb_consignment_file_name<const TRANSFER: bool>(
+    consignment: &Consignment<TRANSFER>,
+    armored: bool,
+) -> String {
+    consignment::file_name(consignment, armored)
+}
+
 pub fn rgb_build_invoice<'a, S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &mut Stock<S, H, P>,
     contract_id: ContractId,
@@ -195,12 +659,14 @@ pub fn rgb_build_invoice<'a, S: StashProvider, H: StateProvider, P: IndexProvide
     beneficiary: Beneficiary,
     transports: impl IntoIterator<Item = &'a str>,
     expiry_secs: Option<u32>,
-    chain_net: rgbinvoice::ChainNet,
-) -> RgbInvoice {
+    network: Network,
+) -> Result<RgbInvoice, NetworkError> {
     use rgbstd::GraphSeal;
     use bp::seals::txout::CloseMethod;
     use commit_verify::Conceal;
 
+    detail::check_contract_network(stock, contract_id.to_raw(), network)?;
+
     let beneficiary = {
         let b = match beneficiary {
             Beneficiary::WitnessVout(vout) => {
@@ -219,7 +685,7 @@ pub fn rgb_build_invoice<'a, S: StashProvider, H: StateProvider, P: IndexProvide
                 rgbinvoice::Beneficiary::BlindedSeal(SecretSeal::from(secret_seal))
             }
         };
-        rgbinvoice::XChainNet::with(chain_net, b)
+        rgbinvoice::XChainNet::with(network.chain_net(), b)
     };
 
     let expiry = {
@@ -229,10 +695,45 @@ pub fn rgb_build_invoice<'a, S: StashProvider, H: StateProvider, P: IndexProvide
             .as_secs();
         (timestamp + expiry_secs.unwrap_or(600) as u64) as i64
     };
-    RgbInvoiceBuilder::rgb20(contract_id.to_raw(), beneficiary)
+    let invoice = RgbInvoiceBuilder::rgb20(contract_id.to_raw(), beneficiary)
         .set_expiry_timestamp(expiry)
         .set_amount_raw(amount)
         .add_transports(transports)
         .unwrap()
-        .finish()
+        .finish();
+    Ok(invoice)
+}
+
+/// Builds an invoice for an amount of `contract`, e.g. one parsed with
+/// `ContractInfo::parse_amount`.
+pub fn rgb_build_invoice_amount<'a, S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    contract: &ContractInfo,
+    amount: &RgbAmount,
+    beneficiary: Beneficiary,
+    transports: impl IntoIterator<Item = &'a str>,
+    expiry_secs: Option<u32>,
+    network: Network,
+) -> Result<RgbInvoice, InvoiceError> {
+    let amount = contract.atomic(amount)?;
+    let invoice = rgb_build_invoice(
+        stock,
+        contract.contract_id,
+        amount,
+        beneficiary,
+        transports,
+        expiry_secs,
+        network,
+    )?;
+    Ok(invoice)
+}
+
+/// Checks that the invoice and its contract, if known to the stock, belong
+/// to `network`.
+pub fn rgb_check_invoice<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    invoice: &RgbInvoice,
+    network: Network,
+) -> Result<(), NetworkError> {
+    detail::check_invoice_network(stock, invoice, network)
 }
@@ -0,0 +1,313 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Write};
+
+use amplify::confinement::U32 as U32MAX;
+use argon2::Argon2;
+use bitcoin::hashes::{sha256, Hash};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use rgbstd::containers::{ConsignmentExt, Kit, ToWitnessId, Transfer};
+use rgbstd::persistence::{
+    ContractStateRead, IndexProvider, StashProvider, StateProvider, Stock,
+};
+use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+use rgbstd::vm::{WitnessOrd, XWitnessTx};
+use rgbstd::{GraphSeal, XChain, XWitnessId};
+use strict_encoding::{StrictDecode, StrictEncode, StrictReader, StrictWriter};
+
+use crate::error::BackupError;
+
+const MAGIC: &[u8; 6] = b"RGBBAK";
+const VERSION: u8 = 1;
+
+const PLAIN: u8 = 0;
+const ENCRYPTED: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+fn encode<T: StrictEncode>(value: &T) -> Vec<u8> {
+    value
+        .strict_encode(StrictWriter::in_memory::<U32MAX>())
+        .expect("in-memory encoding")
+        .unbox()
+        .unconfine()
+}
+
+fn decode<T: StrictDecode>(data: &[u8]) -> Result<T, BackupError> {
+    T::strict_decode(&mut StrictReader::in_memory::<U32MAX>(data))
+        .map_err(|e| BackupError::InvalidData(e.to_string()))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .expect("valid key derivation params");
+    key
+}
+
+// Each section is a list of length-prefixed items.
+fn write_section(payload: &mut Vec<u8>, items: &[Vec<u8>]) {
+    payload.extend((items.len() as u32).to_le_bytes());
+    for item in items {
+        payload.extend((item.len() as u32).to_le_bytes());
+        payload.extend(item);
+    }
+}
+
+fn read_section<'a>(payload: &mut &'a [u8]) -> Result<Vec<&'a [u8]>, BackupError> {
+    fn take<'a>(payload: &mut &'a [u8], len: usize) -> Result<&'a [u8], BackupError> {
+        if payload.len() < len {
+            return Err(BackupError::InvalidData("unexpected end of data".to_string()));
+        }
+        let (data, rest) = payload.split_at(len);
+        *payload = rest;
+        Ok(data)
+    }
+    fn take_len(payload: &mut &[u8]) -> Result<usize, BackupError> {
+        let bytes = take(payload, 4)?.try_into().expect("4 bytes");
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    let count = take_len(payload)?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let len = take_len(payload)?;
+        items.push(take(payload, len)?);
+    }
+    Ok(items)
+}
+
+/// Answers with the witnesses and their ords as they were at backup time,
+/// falling back to `fallback` for the ones not in the backup, like the
+/// witnesses stored without their tx.
+#[derive(Default)]
+struct BackupResolver<'a> {
+    txs: HashMap<XWitnessId, XWitnessTx>,
+    ords: HashMap<XWitnessId, WitnessOrd>,
+    fallback: Option<&'a dyn ResolveWitness>,
+}
+
+impl ResolveWitness for BackupResolver<'_> {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        match (self.txs.get(&witness_id), self.fallback) {
+            (Some(tx), _) => Ok(tx.clone()),
+            (None, Some(fallback)) => fallback.resolve_pub_witness(witness_id),
+            (None, None) => Err(WitnessResolverError::Unknown(witness_id)),
+        }
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        match (self.ords.get(&witness_id), self.fallback) {
+            (Some(ord), _) => Ok(*ord),
+            (None, Some(fallback)) => fallback.resolve_pub_witness_ord(witness_id),
+            (None, None) => Err(WitnessResolverError::Unknown(witness_id)),
+        }
+    }
+}
+
+/// Writes the kits, the contracts with all their valid history, and the
+/// secret seals of the stock.
+pub(crate) fn rgb_backup<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    mut writer: impl Write,
+    passphrase: Option<&str>,
+) -> Result<(), BackupError> {
+    let mut kits = vec![];
+    for info in stock.schemata().map_err(|e| BackupError::Stock(e.to_string()))? {
+        let kit = stock
+            .export_schema(info.id)
+            .map_err(|e| BackupError::Stock(e.to_string()))?;
+        let (kit, _) = kit.split();
+        kits.push(encode(&kit));
+    }
+
+    let mut contracts = vec![];
+    let mut ords = vec![];
+    for info in stock.contracts().map_err(|e| BackupError::Stock(e.to_string()))? {
+        let state = stock
+            .contract_state(info.id)
+            .map_err(|e| BackupError::Stock(e.to_string()))?;
+        let is_valid = |witness_id: Option<XWitnessId>| match witness_id {
+            None => true,
+            Some(witness_id) => state.witness_ord(witness_id).is_some_and(WitnessOrd::is_valid),
+        };
+        let outputs = state
+            .rights_all()
+            .filter(|a| is_valid(a.witness))
+            .map(|a| a.seal)
+            .chain(state.fungible_all().filter(|a| is_valid(a.witness)).map(|a| a.seal))
+            .chain(state.data_all().filter(|a| is_valid(a.witness)).map(|a| a.seal))
+            .chain(state.attach_all().filter(|a| is_valid(a.witness)).map(|a| a.seal))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let transfer = stock
+            .transfer(info.id, outputs, None)
+            .map_err(|e| BackupError::Stock(e.to_string()))?;
+        for bw in &transfer.bundles {
+            let witness_id = bw.pub_witness.to_witness_id();
+            if let Some(ord) = state.witness_ord(witness_id) {
+                ords.push([encode(&witness_id), encode(&ord)].concat());
+            }
+        }
+        contracts.push(encode(&transfer));
+    }
+
+    let seals = stock
+        .as_stash_provider()
+        .secret_seals()
+        .map_err(|e| BackupError::Stock(e.to_string()))?
+        .map(|seal| encode(&seal))
+        .collect::<Vec<_>>();
+
+    let mut payload = vec![];
+    write_section(&mut payload, &kits);
+    write_section(&mut payload, &contracts);
+    write_section(&mut payload, &seals);
+    write_section(&mut payload, &ords);
+
+    let mut archive = MAGIC.to_vec();
+    archive.push(VERSION);
+    match passphrase {
+        None => {
+            archive.push(PLAIN);
+            archive.extend(payload);
+        }
+        Some(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            let mut nonce = [0u8; NONCE_LEN];
+            rand::thread_rng().fill_bytes(&mut salt);
+            rand::thread_rng().fill_bytes(&mut nonce);
+            let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt));
+            let ciphertext = cipher
+                .encrypt(Nonce::from_slice(&nonce), payload.as_slice())
+                .expect("in-memory encryption");
+
+            archive.push(ENCRYPTED);
+            archive.extend(salt);
+            archive.extend(nonce);
+            archive.extend(ciphertext);
+        }
+    }
+    let checksum = sha256::Hash::hash(&archive);
+    archive.extend(checksum.as_byte_array());
+
+    writer.write_all(&archive)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Imports an archive written by `rgb_backup` into `stock`.
+pub(crate) fn rgb_restore<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    mut reader: impl Read,
+    passphrase: Option<&str>,
+    fallback: Option<&dyn ResolveWitness>,
+) -> Result<(), BackupError> {
+    let mut archive = vec![];
+    reader.read_to_end(&mut archive)?;
+
+    let header_len = MAGIC.len() + 2;
+    if archive.len() < header_len + 32 || !archive.starts_with(MAGIC) {
+        return Err(BackupError::NotBackup);
+    }
+    let version = archive[MAGIC.len()];
+    if version > VERSION {
+        return Err(BackupError::UnsupportedVersion(version));
+    }
+    let (content, checksum) = archive.split_at(archive.len() - 32);
+    if sha256::Hash::hash(content).as_byte_array() != checksum {
+        return Err(BackupError::ChecksumMismatch);
+    }
+
+    let body = &content[header_len..];
+    let payload = match content[MAGIC.len() + 1] {
+        PLAIN => body.to_vec(),
+        ENCRYPTED => {
+            let passphrase = passphrase.ok_or(BackupError::PassphraseRequired)?;
+            if body.len() < SALT_LEN + NONCE_LEN {
+                return Err(BackupError::InvalidData("missing encryption params".to_string()));
+            }
+            let (salt, rest) = body.split_at(SALT_LEN);
+            let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+            let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt));
+            cipher
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| BackupError::WrongPassphrase)?
+        }
+        method => {
+            return Err(BackupError::InvalidData(format!(
+                "unknown encryption method {method}"
+            )))
+        }
+    };
+
+    let mut payload = payload.as_slice();
+    let kits = read_section(&mut payload)?;
+    let contracts = read_section(&mut payload)?;
+    let seals = read_section(&mut payload)?;
+    let ords = read_section(&mut payload)?;
+
+    for kit in kits {
+        let kit = decode::<Kit>(kit)?
+            .validate()
+            .map_err(|(status, _)| BackupError::InvalidData(status.to_string()))?;
+        stock
+            .import_kit(kit)
+            .map_err(|e| BackupError::Stock(e.to_string()))?;
+    }
+
+    let mut resolver = BackupResolver {
+        fallback,
+        ..Default::default()
+    };
+    for ord in ords {
+        let mut reader = StrictReader::in_memory::<U32MAX>(ord);
+        let (witness_id, ord) = XWitnessId::strict_decode(&mut reader)
+            .and_then(|witness_id| Ok((witness_id, WitnessOrd::strict_decode(&mut reader)?)))
+            .map_err(|e| BackupError::InvalidData(e.to_string()))?;
+        resolver.ords.insert(witness_id, ord);
+    }
+    let transfers = contracts
+        .into_iter()
+        .map(decode::<Transfer>)
+        .collect::<Result<Vec<_>, _>>()?;
+    for transfer in &transfers {
+        for bw in &transfer.bundles {
+            if let XChain::Bitcoin(ref pub_witness) = bw.pub_witness {
+                if let Some(tx) = pub_witness.tx() {
+                    resolver
+                        .txs
+                        .insert(bw.pub_witness.to_witness_id(), XChain::Bitcoin(tx.clone()));
+                }
+            }
+        }
+    }
+    for transfer in transfers {
+        let contract_id = transfer.contract_id();
+        let testnet = transfer.genesis.testnet;
+        let transfer = transfer
+            .validate(&resolver, testnet)
+            .map_err(|(status, _)| BackupError::InvalidContract(contract_id, status.to_string()))?;
+        stock
+            .accept_transfer(transfer, &resolver)
+            .map_err(|e| BackupError::Stock(e.to_string()))?;
+    }
+
+    for seal in seals {
+        stock
+            .store_secret_seal(decode::<XChain<GraphSeal>>(seal)?)
+            .map_err(|e| BackupError::Stock(e.to_string()))?;
+    }
+
+    Ok(())
+}
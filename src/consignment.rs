@@ -0,0 +1,133 @@
+use std::io::{Read, Write};
+
+use armor::{
+    ArmorHeader, ArmorParseError, AsciiArmor, StrictArmorError, ASCII_ARMOR_CHECKSUM_SHA256,
+    ASCII_ARMOR_ID,
+};
+use rgbstd::containers::{
+    Consignment, FileContent, LoadError, ValidConsignment, ASCII_ARMOR_CONSIGNMENT_TYPE,
+    ASCII_ARMOR_CONTRACT, ASCII_ARMOR_VERSION,
+};
+use rgbstd::validation::ResolveWitness;
+
+use crate::detail;
+use crate::error::ConsignmentError;
+use crate::types::Network;
+
+// Headers which must be present and match the armored consignment.
+const CHECKED_HEADERS: [&str; 4] = [
+    ASCII_ARMOR_ID,
+    ASCII_ARMOR_VERSION,
+    ASCII_ARMOR_CONSIGNMENT_TYPE,
+    ASCII_ARMOR_CONTRACT,
+];
+
+/// File name of a consignment, binary files have the `rgb` extension and
+/// ASCII-armored ones the `rgba` extension.
+pub(crate) fn file_name<const TRANSFER: bool>(
+    consignment: &Consignment<TRANSFER>,
+    armored: bool,
+) -> String {
+    let extension = if armored { "rgba" } else { "rgb" };
+    // Without the `rgb:csg:` prefix and the mnemonic, which don't fit file names.
+    format!("{:-#}.{extension}", consignment.consignment_id())
+}
+
+pub(crate) fn write<const TRANSFER: bool>(
+    consignment: &Consignment<TRANSFER>,
+    writer: impl Write,
+) -> Result<(), ConsignmentError>
+where
+    Consignment<TRANSFER>: FileContent,
+{
+    Ok(consignment.save(writer)?)
+}
+
+pub(crate) fn read<const TRANSFER: bool>(
+    reader: impl Read,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidConsignment<TRANSFER>, ConsignmentError>
+where
+    Consignment<TRANSFER>: FileContent,
+{
+    let consignment = Consignment::<TRANSFER>::load(reader).map_err(|e| match e {
+        LoadError::InvalidMagic => {
+            ConsignmentError::InvalidData(format!("not a {} file", kind(TRANSFER)))
+        }
+        e => ConsignmentError::InvalidData(e.to_string()),
+    })?;
+    validate(consignment, resolver, network)
+}
+
+pub(crate) fn armor<const TRANSFER: bool>(consignment: &Consignment<TRANSFER>) -> String {
+    consignment.to_ascii_armored_string()
+}
+
+pub(crate) fn unarmor<const TRANSFER: bool>(
+    s: &str,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidConsignment<TRANSFER>, ConsignmentError> {
+    let consignment = Consignment::<TRANSFER>::from_ascii_armored_str(s).map_err(|e| match e {
+        StrictArmorError::Armor(ArmorParseError::MismatchedChecksum) => {
+            ConsignmentError::ChecksumMismatch
+        }
+        StrictArmorError::MismatchedId { .. } => {
+            ConsignmentError::HeaderMismatch(ASCII_ARMOR_ID.to_string())
+        }
+        e => ConsignmentError::InvalidArmor(e.to_string()),
+    })?;
+
+    // The armor parser skips the checksum when it's missing and only checks
+    // the id among the other headers.
+    let headers = armor_headers::<TRANSFER>(s);
+    if !headers
+        .iter()
+        .any(|header| header.title == ASCII_ARMOR_CHECKSUM_SHA256)
+    {
+        return Err(ConsignmentError::MissingChecksum);
+    }
+    for expected in consignment.ascii_armored_headers() {
+        if !CHECKED_HEADERS.contains(&expected.title.as_str()) {
+            continue;
+        }
+        if !headers.contains(&expected) {
+            return Err(ConsignmentError::HeaderMismatch(expected.title));
+        }
+    }
+
+    validate(consignment, resolver, network)
+}
+
+fn armor_headers<const TRANSFER: bool>(s: &str) -> Vec<ArmorHeader> {
+    let first = format!("-----BEGIN {}-----", Consignment::<TRANSFER>::PLATE_TITLE);
+    s.lines()
+        .skip_while(|line| *line != first)
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.parse().ok())
+        .collect()
+}
+
+fn validate<const TRANSFER: bool>(
+    consignment: Consignment<TRANSFER>,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidConsignment<TRANSFER>, ConsignmentError> {
+    if consignment.transfer != TRANSFER {
+        return Err(ConsignmentError::InvalidData(format!(
+            "consignment is not a {}",
+            kind(TRANSFER)
+        )));
+    }
+    Ok(detail::validate_consignment(consignment, resolver, network)?)
+}
+
+fn kind(transfer: bool) -> &'static str {
+    if transfer {
+        "transfer"
+    } else {
+        "contract"
+    }
+}
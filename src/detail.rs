@@ -16,6 +16,7 @@ use commit_verify::mpc::{self, MPC_MINIMAL_DEPTH};
 use commit_verify::CommitId as _;
 use commit_verify::TryCommitVerify;
 use ifaces::IssuerWrapper;
+use ifaces::Rgb20Wrapper;
 use rand::Rng;
 use rgbstd::containers::BundleDichotomy;
 use rgbstd::containers::Fascia;
@@ -25,35 +26,48 @@ use rgbstd::containers::TransitionInfoError;
 use rgbstd::containers::ValidContract;
 use rgbstd::interface::BuilderError;
 use rgbstd::interface::ContractBuilder;
+use rgbstd::interface::FilterIncludeAll;
 use rgbstd::interface::IfaceClass;
+use rgbstd::interface::IfaceWrapper;
 use rgbstd::persistence::ComposeError;
+use rgbstd::persistence::ContractStateRead;
 use rgbstd::persistence::PersistedState;
+use rgbstd::persistence::StashInconsistency;
 use rgbstd::persistence::StockError;
 use rgbstd::stl::AssetSpec;
 use rgbstd::stl::ContractTerms;
-use rgbstd::stl::RicardianContract;
 use rgbstd::Amount;
 use rgbstd::BlindingFactor;
 use rgbstd::GenesisSeal;
 use rgbstd::Identity;
+use rgbstd::Operation as _;
 use rgbstd::Opout;
 use rgbstd::OutputSeal;
 use rgbstd::Precision;
 use rgbstd::SecretSeal;
 use rgbstd::Transition;
 use rgbstd::TransitionBundle;
+use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+use rgbstd::vm::{WitnessOrd, XWitnessTx};
 use rgbstd::Vin;
 use rgbstd::XChain;
+use rgbstd::XWitnessId;
 use rgbstd::{
     containers::{AnchorSet, BuilderSeal, TransitionInfo},
     persistence::{IndexProvider, StashProvider, StateProvider, Stock},
     ContractId, GraphSeal, InputMap, OpId, XOutpoint, XOutputSeal,
 };
+use rgbinvoice::RgbInvoice;
+use rgbstd::containers::{Consignment, ValidConsignment};
+use rgbstd::{AltLayer1, Genesis};
 use schemata::NonInflatableAsset;
 use strict_types::encoding::TypeName;
 
 use bp::{ConsensusDecode as _, Tx};
 
+use crate::error::{NetworkError, RbfError, StockFailure, TransferError, ValidationError};
+use crate::types::{AllocatedState, HistoryAllocation, HistoryEntry, Network};
+use crate::resolvers::FasciaResolver;
 use crate::ToRaw;
 
 
@@ -94,7 +108,374 @@ pub(crate) fn rgb_balance<S: StashProvider, H: StateProvider, P: IndexProvider>(
 pub(crate) fn filter_rgb_outpoints<S: StashProvider, H: StateProvider, P: IndexProvider>(
     stock: &Stock<S, H, P>,
     utxos: &[XOutpoint],
-) -> HashSet<XOutpoint> {
+) -> Result<HashSet<XOutpoint>, StockFailure> {
+    let mut rgb_outpoints = HashSet::new();
+    for contract_info in stock.contracts().map_err(|e| StockFailure(e.to_string()))? {
+        let Some((contract, _, _)) = rgb20_contract(stock, contract_info.id)? else {
+            continue;
+        };
+        rgb_outpoints.extend(contract.allocations(utxos).map(|o| o.seal.to_outpoint()));
+    }
+
+    Ok(rgb_outpoints)
+}
+
+// Every valid assignment of all the contracts which is sealed to one of `utxos`.
+pub(crate) fn rgb_allocations<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    utxos: &[XOutpoint],
+) -> Result<Vec<(XOutpoint, ContractId, Opout, AllocatedState)>, StockFailure> {
+    let utxos = utxos.iter().collect::<HashSet<_>>();
+
+    let mut allocations = vec![];
+    for contract_info in stock.contracts().map_err(|e| StockFailure(e.to_string()))? {
+        let contract_id = contract_info.id;
+        let state = stock
+            .contract_state(contract_id)
+            .map_err(|e| StockFailure(e.to_string()))?;
+        let mut push = |witness: Option<XWitnessId>, seal: XOutputSeal, opout, allocated| {
+            let is_valid = match witness {
+                None => true,
+                Some(witness_id) => state.witness_ord(witness_id).is_some_and(WitnessOrd::is_valid),
+            };
+            let outpoint = seal.to_outpoint();
+            if is_valid && utxos.contains(&outpoint) {
+                allocations.push((outpoint, contract_id, opout, allocated));
+            }
+        };
+
+        for a in state.rights_all() {
+            push(a.witness, a.seal, a.opout, AllocatedState::Void);
+        }
+        for a in state.fungible_all() {
+            push(a.witness, a.seal, a.opout, AllocatedState::Amount(a.state.value.as_u64()));
+        }
+        for a in state.data_all() {
+            push(a.witness, a.seal, a.opout, AllocatedState::Data(a.state.value.to_vec()));
+        }
+        for a in state.attach_all() {
+            let file = &a.state.file;
+            let allocated = AllocatedState::Attachment {
+                id: file.id.to_byte_array(),
+                media_type: file.media_type.to_string(),
+            };
+            push(a.witness, a.seal, a.opout, allocated);
+        }
+    }
+
+    Ok(allocations)
+}
+
+// None if the contract is unknown, an error only if the stock can't be read.
+fn known_contract_info<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+) -> Result<Option<rgbstd::info::ContractInfo>, StockFailure> {
+    match stock.contract_info(contract_id) {
+        Ok(info) => Ok(Some(info)),
+        Err(StockError::StashInconsistency(StashInconsistency::ContractAbsent(_))) => Ok(None),
+        Err(e) => Err(StockFailure(e.to_string())),
+    }
+}
+
+// None if the contract is unknown or doesn't implement RGB20, an error only
+// if the stock can't be read.
+#[allow(clippy::type_complexity)]
+fn rgb20_contract<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+) -> Result<
+    Option<(Rgb20Wrapper<H::ContractRead<'_>>, Vec<TypeName>, rgbstd::info::ContractInfo)>,
+    StockFailure,
+> {
+    let iface_name = TypeName::from("RGB20Fixed");
+
+    let Some(info) = known_contract_info(stock, contract_id)? else {
+        return Ok(None);
+    };
+    let ifaces = stock
+        .schema(info.schema_id)
+        .map_err(|e| StockFailure(e.to_string()))?
+        .iimpls
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    if !ifaces.contains(&iface_name) {
+        return Ok(None);
+    }
+
+    let contract = stock
+        .contract_iface(contract_id, iface_name)
+        .map_err(|e| StockFailure(e.to_string()))?;
+    Ok(Some((Rgb20Wrapper::with(contract), ifaces, info)))
+}
+
+// Storage errors read as an unknown contract here.
+pub(crate) fn rgb_contract_terms<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+) -> Option<ContractTerms> {
+    let (contract, _, _) = rgb20_contract(stock, contract_id).ok()??;
+    Some(contract.contract_terms())
+}
+
+// Storage errors read as an unknown contract here.
+pub(crate) fn rgb_precision_and_ticker<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+) -> Option<(u8, String)> {
+    let (contract, _, _) = rgb20_contract(stock, contract_id).ok()??;
+    let spec = contract.spec();
+    Some((spec.precision.decimals(), spec.ticker.to_string()))
+}
+
+pub(crate) fn rgb_contract_info<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    network: Network,
+) -> Result<Option<crate::types::ContractInfo>, StockFailure> {
+    let Some((contract, ifaces, info)) = rgb20_contract(stock, contract_id)? else {
+        return Ok(None);
+    };
+    if check_contract_network(stock, contract_id, network).is_err() {
+        return Ok(None);
+    }
+    let spec = contract.spec();
+    let terms = contract.contract_terms();
+
+    Ok(Some(crate::types::ContractInfo {
+        contract_id: contract_id.into(),
+        issuer: info.issuer.to_string(),
+        ticker: spec.ticker.to_string(),
+        name: spec.name.to_string(),
+        details: spec.details.as_ref().map(ToString::to_string),
+        terms: terms.text.to_string(),
+        precision: spec.precision.decimals(),
+        issued_supply: contract.total_issued_supply().value(),
+        genesis_timestamp: info.issued_at.timestamp(),
+        network,
+        ifaces: ifaces.iter().map(ToString::to_string).collect(),
+    }))
+}
+
+// Genesis only records the testnet flag and the Liquid layer, so mainnet,
+// Liquid and the test networks are told apart, but not the test networks
+// between them, e.g. a regtest contract passes for signet.
+pub(crate) fn check_genesis_network(genesis: &Genesis, network: Network) -> Result<(), NetworkError> {
+    let liquid = genesis.alt_layers1.iter().any(|layer| *layer == AltLayer1::Liquid);
+    if genesis.testnet != network.is_testnet() || liquid != (network == Network::Liquid) {
+        return Err(NetworkError::ContractMismatch {
+            contract_id: genesis.contract_id().into(),
+            network,
+        });
+    }
+    Ok(())
+}
+
+// Contracts missing from the stock are not checked, e.g. the one of an
+// invoice for a first receive, they are checked when their transfer is validated.
+pub(crate) fn check_contract_network<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    network: Network,
+) -> Result<(), NetworkError> {
+    match stock.as_stash_provider().genesis(contract_id) {
+        Ok(genesis) => check_genesis_network(genesis, network),
+        Err(_) => Ok(()),
+    }
+}
+
+pub(crate) fn check_invoice_network<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    invoice: &RgbInvoice,
+    network: Network,
+) -> Result<(), NetworkError> {
+    if invoice.chain_network() != network.chain_net() {
+        return Err(NetworkError::InvoiceMismatch {
+            expected: network,
+            found: invoice.chain_network(),
+        });
+    }
+    match invoice.contract {
+        Some(contract_id) => check_contract_network(stock, contract_id, network),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn validate_consignment<const TRANSFER: bool>(
+    consignment: Consignment<TRANSFER>,
+    resolver: &impl ResolveWitness,
+    network: Network,
+) -> Result<ValidConsignment<TRANSFER>, ValidationError> {
+    check_genesis_network(&consignment.genesis, network)?;
+    consignment
+        .validate(resolver, network.is_testnet())
+        .map_err(|(status, _)| ValidationError::Invalid(status))
+}
+
+// Genesis and all the known transitions of the contract, in the order they
+// are applied to the state.
+pub(crate) fn rgb_history<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    contract_id: ContractId,
+    owned_outpoints: &[XOutpoint],
+) -> Result<Option<Vec<HistoryEntry>>, StockFailure> {
+    let owned_outpoints = owned_outpoints.iter().collect::<HashSet<_>>();
+    let stash = stock.as_stash_provider();
+    let index = stock.as_index_provider();
+    if known_contract_info(stock, contract_id)?.is_none() {
+        return Ok(None);
+    }
+    let state = stock
+        .contract_state(contract_id)
+        .map_err(|e| StockFailure(e.to_string()))?;
+
+    let mut allocations: HashMap<Opout, HistoryAllocation> = HashMap::new();
+    let mut outputs: HashMap<OpId, Vec<HistoryAllocation>> = HashMap::new();
+    for a in state.fungible_all() {
+        let allocation = HistoryAllocation {
+            opout: a.opout.into(),
+            outpoint: a.seal.to_outpoint().into(),
+            amount: a.state.value.as_u64(),
+        };
+        allocations.insert(a.opout, allocation);
+        outputs.entry(a.opout.op).or_default().push(allocation);
+    }
+
+    let genesis_id = stash
+        .genesis(contract_id)
+        .map_err(|e| StockFailure(e.to_string()))?
+        .id();
+    let mut ops = vec![(genesis_id, None, vec![])];
+    for bundle_id in stash.bundle_ids().map_err(|e| StockFailure(e.to_string()))? {
+        let (witness_ids, bundle_contract_id) = index
+            .bundle_info(bundle_id)
+            .map_err(|e| StockFailure(e.to_string()))?;
+        if bundle_contract_id != contract_id {
+            continue;
+        }
+        // An RBFed bundle has several witnesses, only one of them is valid.
+        let witness_ids = witness_ids.collect::<Vec<_>>();
+        let witness_id = witness_ids
+            .iter()
+            .copied()
+            .find(|id| state.witness_ord(*id).is_some_and(WitnessOrd::is_valid))
+            .or(witness_ids.first().copied());
+
+        let archived = !witness_id
+            .and_then(|id| state.witness_ord(id))
+            .is_some_and(WitnessOrd::is_valid);
+
+        let bundle = stash
+            .bundle(bundle_id)
+            .map_err(|e| StockFailure(e.to_string()))?;
+        for (opid, transition) in bundle.known_transitions.iter() {
+            let inputs = transition.inputs.iter().map(|input| input.prev_out).collect();
+            ops.push((*opid, witness_id, inputs));
+
+            // Outputs of archived operations are not part of the contract state,
+            // they are read from the transition itself.
+            if archived {
+                let archived_outputs = transition.assignments.iter().flat_map(|(ty, assigns)| {
+                    assigns.as_fungible().iter().enumerate().filter_map(move |(no, assign)| {
+                        let (seal, state) = assign.as_revealed()?;
+                        let seal = seal.try_to_output_seal(witness_id?).ok()?;
+                        Some(HistoryAllocation {
+                            opout: Opout::new(*opid, *ty, no as u16).into(),
+                            outpoint: seal.to_outpoint().into(),
+                            amount: state.value.as_u64(),
+                        })
+                    })
+                });
+                outputs.entry(*opid).or_default().extend(archived_outputs);
+            }
+        }
+    }
+
+    let spent_ops = ops
+        .iter()
+        .map(|(opid, _, inputs)| {
+            let spent = inputs.iter().map(|input| input.op.to_byte_array()).collect();
+            (opid.to_byte_array(), spent)
+        })
+        .collect::<HashMap<[u8; 32], HashSet<[u8; 32]>>>();
+
+    let mut history = ops
+        .into_iter()
+        .map(|(opid, witness_id, inputs): (OpId, Option<XWitnessId>, Vec<Opout>)| {
+            let witness_ord = witness_id.and_then(|id| state.witness_ord(id));
+            let inputs = inputs
+                .iter()
+                .filter_map(|opout| allocations.get(opout).copied())
+                .collect::<Vec<_>>();
+            let mut outputs = outputs.remove(&opid).unwrap_or_default();
+            outputs.sort();
+
+            let owned_amount = |allocations: &[HistoryAllocation]| -> i128 {
+                allocations
+                    .iter()
+                    .filter(|a| owned_outpoints.contains(&a.outpoint.to_raw()))
+                    .map(|a| a.amount as i128)
+                    .sum()
+            };
+            // Archived operations didn't move anything, counting them would
+            // count twice the amounts of their replacements.
+            let net_change = match witness_ord {
+                Some(ord) if !ord.is_valid() => 0,
+                _ => owned_amount(&outputs) - owned_amount(&inputs),
+            };
+
+            HistoryEntry {
+                opid: opid.to_byte_array(),
+                witness_txid: witness_id.map(|id| match id {
+                    XWitnessId::Bitcoin(txid) | XWitnessId::Liquid(txid) => txid.into(),
+                }),
+                witness_ord,
+                inputs,
+                outputs,
+                net_change,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Genesis goes first, then mined, tentative and archived operations.
+    history.sort_by_key(|entry| {
+        let rank = match (entry.witness_txid, entry.witness_ord) {
+            (None, _) => (0, None),
+            (Some(_), Some(WitnessOrd::Mined(pos))) => (1, Some(pos)),
+            (Some(_), Some(WitnessOrd::Tentative)) => (2, None),
+            (Some(_), _) => (3, None),
+        };
+        (rank, entry.opid)
+    });
+
+    // Operations of the same rank, e.g. in the same block or all tentative,
+    // come after the ones they spend.
+    let mut ordered = Vec::with_capacity(history.len());
+    let mut listed = HashSet::new();
+    let mut pending = history;
+    while !pending.is_empty() {
+        let next = pending
+            .iter()
+            .position(|entry| {
+                spent_ops[&entry.opid]
+                    .iter()
+                    .all(|spent| listed.contains(spent) || !spent_ops.contains_key(spent))
+            })
+            .unwrap_or(0);
+        let entry = pending.remove(next);
+        listed.insert(entry.opid);
+        ordered.push(entry);
+    }
+
+    Ok(Some(ordered))
+}
+
+// Balances of all the RGB20 contracts, by outpoint. Other contracts, e.g.
+// non fungible ones, are skipped.
+fn rgb_balances<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+) -> Result<BTreeMap<(ContractId, XOutpoint), u64>, StockFailure> {
     let iface_name = TypeName::from("RGB20Fixed");
     let iface = stock.iface(iface_name.clone()).unwrap();
     let operation = iface.default_operation.as_ref().unwrap();
@@ -105,26 +486,68 @@ pub(crate) fn filter_rgb_outpoints<S: StashProvider, H: StateProvider, P: IndexP
         .and_then(|t| t.default_assignment.clone())
         .unwrap();
 
-    let mut rgb_outpoints = vec![];
-    for contract_info in stock.contracts().unwrap() {
+    let mut balances = BTreeMap::new();
+    let contracts = stock
+        .contracts()
+        .map_err(|e| StockFailure(e.to_string()))?;
+    for contract_info in contracts {
         let contract_id = contract_info.id;
 
+        let schema = stock
+            .schema(contract_info.schema_id)
+            .map_err(|e| StockFailure(e.to_string()))?;
+        if !schema.iimpls.contains_key(&iface_name) {
+            continue;
+        }
+
         let contract = stock
             .contract_iface(contract_id, iface_name.clone())
-            .unwrap();
-        // .map_err(|e| e.to_string())?;
+            .map_err(|e| StockFailure(e.to_string()))?;
+        let allocations = contract
+            .fungible(assignment_name.clone(), FilterIncludeAll)
+            .map_err(|e| StockFailure(e.to_string()))?;
+        for allocation in allocations {
+            let amount: u64 = allocation.state.into();
+            *balances
+                .entry((contract_id, allocation.seal.to_outpoint()))
+                .or_default() += amount;
+        }
+    }
 
-        rgb_outpoints.extend(
-            contract
-                .fungible(assignment_name.clone(), utxos)
-                .unwrap()
-                .map(|o| o.seal.to_outpoint())
-        );
+    Ok(balances)
+}
+
+/// Re-resolves the ordering of all the known witnesses.
+///
+/// Returns the balances which changed, as `(before, after)`, together with
+/// the witnesses which failed to resolve.
+#[allow(clippy::type_complexity)]
+pub(crate) fn rgb_update_witnesses<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    resolver: impl ResolveWitness,
+) -> Result<
+    (
+        BTreeMap<(ContractId, XOutpoint), (u64, u64)>,
+        HashMap<XWitnessId, String>,
+    ),
+    StockFailure,
+> {
+    let before = rgb_balances(stock)?;
+    let update_res = stock
+        .update_witnesses(resolver, 0)
+        .map_err(|e| StockFailure(e.to_string()))?;
+    let after = rgb_balances(stock)?;
+
+    let mut changes = BTreeMap::new();
+    for key in before.keys().chain(after.keys()) {
+        let old = before.get(key).copied().unwrap_or_default();
+        let new = after.get(key).copied().unwrap_or_default();
+        if old != new {
+            changes.insert(*key, (old, new));
+        }
     }
 
-    rgb_outpoints
-        .into_iter()
-        .collect()
+    Ok((changes, update_res.failed))
 }
 
 pub(crate) fn rgb_coin_select<S: StashProvider, H: StateProvider, P: IndexProvider>(
@@ -145,7 +568,7 @@ pub(crate) fn rgb_coin_select<S: StashProvider, H: StateProvider, P: IndexProvid
 
     let mut selected_prev_outputs: Vec<XOutputSeal> = vec![];
     for (&contract_id, rgb_assignment) in &rgb_assignments.0 {
-        let total_amount_needed: u64 = rgb_assignment.iter().map(|(_, amount)| *amount).sum();
+        let total_amount_needed: u64 = rgb_assignment.values().sum();
         let mut total_amount_collected = Amount::ZERO;
 
         let contract = stock
@@ -334,7 +757,7 @@ pub(crate) fn rgb_compose<S: StashProvider, H: StateProvider, P: IndexProvider,
     Ok(transition_info_list)
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PartialFascia {
     anchor_set: AnchorSet,
     bundles: NonEmptyOrdMap<ContractId, BundleDichotomy, U24>,
@@ -344,7 +767,10 @@ impl PartialFascia {
     #[must_use]
     pub fn complete_with_tx(self, consensus_serialized_tx: &[u8]) -> Fascia {
         let tx = Tx::consensus_deserialize(consensus_serialized_tx).unwrap();
-        let witness = PubWitness::with(tx);
+        self.complete_with(PubWitness::with(tx))
+    }
+
+    fn complete_with(self, witness: PubWitness) -> Fascia {
         Fascia {
             witness: XChain::with(rgbstd::Layer1::Bitcoin, witness),
             anchor: self.anchor_set,
@@ -352,15 +778,17 @@ impl PartialFascia {
         }
     }
 
+    pub(crate) fn commitment(&self) -> mpc::Commitment {
+        match self.anchor_set {
+            AnchorSet::Opret(ref anchor) => anchor.mpc_proof.commit_id(),
+            _ => unreachable!("only opret is supported"),
+        }
+    }
+
     #[must_use]
     pub fn complete_with_txid(self, txid: impl Into<crate::types::Txid>) -> Fascia {
         let txid = txid.into().to_raw();
-        let witness = PubWitness::new(txid);
-        Fascia {
-            witness: XChain::with(rgbstd::Layer1::Bitcoin, witness),
-            anchor: self.anchor_set,
-            bundles: self.bundles,
-        }
+        self.complete_with(PubWitness::new(txid))
     }
 }
 
@@ -489,23 +917,98 @@ pub(crate) fn rgb_commit(
     (commitment, partial_fascia)
 }
 
+// Answers only for the witness replaced by RBF, so that the other witnesses
+// keep their ordering on `Stock::update_witnesses`.
+struct ReplacedWitness(XWitnessId);
+
+impl ResolveWitness for ReplacedWitness {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        Err(WitnessResolverError::Unknown(witness_id))
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        if witness_id == self.0 {
+            Ok(WitnessOrd::Archived)
+        } else {
+            Err(WitnessResolverError::Unknown(witness_id))
+        }
+    }
+}
+
+pub(crate) fn rgb_rbf_recolor<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &mut Stock<S, H, P>,
+    partial_fascia: PartialFascia,
+    replaced_tx: &Tx,
+    replacement_tx: &Tx,
+) -> Result<(), RbfError> {
+    if replaced_tx.txid() == replacement_tx.txid() {
+        return Err(RbfError::NotReplaced);
+    }
+
+    // The commitment is in the first OP_RETURN output, see `CloseMethod::OpretFirst`.
+    let commitment_output = |tx: &Tx| {
+        tx.outputs
+            .iter()
+            .enumerate()
+            .find(|(_, txout)| txout.script_pubkey.is_op_return())
+            .map(|(vout, txout)| (vout, txout.script_pubkey.clone()))
+    };
+    let Some(replaced_output) = commitment_output(replaced_tx) else {
+        return Err(RbfError::NoCommitment);
+    };
+    let commitment = partial_fascia.commitment().to_byte_array();
+    if replaced_output.1 != bp::ScriptPubkey::op_return(&commitment) {
+        return Err(RbfError::FasciaMismatch);
+    }
+    if commitment_output(replacement_tx) != Some(replaced_output) {
+        return Err(RbfError::CommitmentChanged);
+    }
+
+    let prev_outputs = |tx: &Tx| {
+        tx.inputs
+            .iter()
+            .map(|txin| txin.prev_output)
+            .collect::<Vec<_>>()
+    };
+    if prev_outputs(replaced_tx) != prev_outputs(replacement_tx) {
+        return Err(RbfError::InputsChanged);
+    }
+
+    let fascia = partial_fascia.complete_with(PubWitness::with(replacement_tx.clone()));
+    let resolver = FasciaResolver::new(&fascia);
+    stock
+        .consume_fascia(fascia, resolver)
+        .map_err(|e| RbfError::Stock(e.to_string()))?;
+
+    let replaced_id = XWitnessId::Bitcoin(replaced_tx.txid());
+    stock
+        .update_witnesses(ReplacedWitness(replaced_id), 0)
+        .map_err(|e| RbfError::Stock(e.to_string()))?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn rgb_issue(
     issuer: &str,
     ticker: &str,
     name: &str,
     details: Option<&str>,
     precision: u8,
+    terms: ContractTerms,
     allocations: impl IntoIterator<Item = (String, u64)>,
-    is_testnet: bool,
+    network: Network,
 ) -> ValidContract {
     let issuer = Identity::from_str(issuer).unwrap();
     let precision = Precision::try_from(precision).unwrap();
 
     let spec = AssetSpec::with(ticker, name, precision, details).unwrap();
-    let terms = ContractTerms {
-        text: RicardianContract::default(),
-        media: None,
-    };
 
     let iface = NonInflatableAsset::FEATURES.iface();
     let schema = NonInflatableAsset::schema();
@@ -537,9 +1040,12 @@ pub(crate) fn rgb_issue(
         .add_global_state("terms", terms)
         .unwrap();
     
-    if !is_testnet {
+    if !network.is_testnet() {
         builder = builder.set_mainnet();
     }
+    if network == Network::Liquid {
+        builder = builder.add_layer1(AltLayer1::Liquid).unwrap();
+    }
 
     builder.issue_contract().unwrap()
 }
@@ -550,8 +1056,86 @@ pub(crate) fn rgb_transfer<S: StashProvider, H: StateProvider, P: IndexProvider>
     contract_id: ContractId,
     outputs: &[XOutputSeal],
     secret_seal: Option<XChain<SecretSeal>>,
-) -> Transfer {
-    stock.transfer(contract_id, outputs, secret_seal).unwrap()
+) -> Result<Transfer, String> {
+    stock
+        .transfer(contract_id, outputs, secret_seal)
+        .map_err(|e| e.to_string())
+}
+
+// Every requested contract is consigned with its own outputs and secret seal.
+// Any other contract assigning state to one of the requested outputs or seals,
+// e.g. moved there by a blank transition, is consigned as well, while the ones
+// only moved elsewhere, like to the sender's change, are left out.
+#[allow(clippy::type_complexity)]
+pub(crate) fn rgb_transfer_many<S: StashProvider, H: StateProvider, P: IndexProvider>(
+    stock: &Stock<S, H, P>,
+    requests: &[(ContractId, Vec<XOutpoint>, Option<XChain<SecretSeal>>)],
+    network: Network,
+) -> Result<BTreeMap<ContractId, Transfer>, TransferError> {
+    let stash = stock.as_stash_provider();
+    let index = stock.as_index_provider();
+
+    let mut consigned =
+        BTreeMap::<ContractId, (BTreeSet<XOutpoint>, Option<XChain<SecretSeal>>)>::new();
+    for (contract_id, outputs, secret_seal) in requests {
+        let (seals, seal) = consigned.entry(*contract_id).or_default();
+        seals.extend(outputs.iter().copied());
+        *seal = seal.or(*secret_seal);
+    }
+    let outputs = requests
+        .iter()
+        .flat_map(|(_, outputs, _)| outputs.iter().copied())
+        .collect::<BTreeSet<_>>();
+    let secret_seals = requests
+        .iter()
+        .filter_map(|(_, _, secret_seal)| *secret_seal)
+        .collect::<Vec<_>>();
+
+    for bundle_id in stash.bundle_ids().map_err(|e| TransferError::Stock(e.to_string()))? {
+        let (witness_ids, contract_id) = index
+            .bundle_info(bundle_id)
+            .map_err(|e| TransferError::Stock(e.to_string()))?;
+        let witness_ids = witness_ids.collect::<Vec<_>>();
+        let bundle = stash
+            .bundle(bundle_id)
+            .map_err(|e| TransferError::Stock(e.to_string()))?;
+        for transition in bundle.known_transitions.values() {
+            for assigns in transition.assignments.values() {
+                let concealed = assigns.to_confidential_seals();
+                let secret_seal = secret_seals.iter().find(|s| concealed.contains(s)).copied();
+                let revealed = (0..assigns.len_u16())
+                    .filter_map(|no| assigns.revealed_seal_at(no).ok().flatten())
+                    .flat_map(|seal| {
+                        witness_ids
+                            .iter()
+                            .filter_map(move |id| seal.try_to_output_seal(*id).ok())
+                    })
+                    .map(|seal| seal.to_outpoint())
+                    .filter(|o| outputs.contains(o))
+                    .collect::<Vec<_>>();
+                if revealed.is_empty() && secret_seal.is_none() {
+                    continue;
+                }
+                let (seals, seal) = consigned.entry(contract_id).or_default();
+                seals.extend(revealed);
+                *seal = seal.or(secret_seal);
+            }
+        }
+    }
+
+    let mut transfers = BTreeMap::new();
+    for (contract_id, (seals, secret_seal)) in consigned {
+        check_contract_network(stock, contract_id, network)?;
+
+        let outputs = seals
+            .into_iter()
+            .map(|o| o.map(|o| OutputSeal::new(CloseMethod::OpretFirst, o)))
+            .collect::<Vec<_>>();
+        let transfer = rgb_transfer(stock, contract_id, &outputs, secret_seal)
+            .map_err(TransferError::Stock)?;
+        transfers.insert(contract_id, transfer);
+    }
+    Ok(transfers)
 }
 
 #[inline]
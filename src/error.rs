@@ -1 +1,406 @@
-// TODO
+// TODO: move the other errors here
+
+use rgbinvoice::ChainNet;
+use rgbstd::validation::Status;
+
+use crate::types::{ContractId, Network};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RbfError {
+    /// Replacement tx has the same txid as the replaced one.
+    NotReplaced,
+    /// Tx has no OP_RETURN output to carry the commitment.
+    NoCommitment,
+    /// Replaced tx doesn't commit to the bundles of the fascia.
+    FasciaMismatch,
+    /// Commitment output was moved or changed by the replacement tx.
+    CommitmentChanged,
+    /// Replacement tx doesn't spend the same inputs in the same order.
+    InputsChanged,
+    /// Tx can't be deserialized.
+    Decode(String),
+    /// Stock failed to consume the new fascia or to archive the replaced
+    /// witness.
+    Stock(String),
+}
+
+impl std::fmt::Display for RbfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotReplaced => write!(f, "replacement tx is the same as the replaced one"),
+            Self::NoCommitment => write!(f, "tx has no commitment output"),
+            Self::FasciaMismatch => write!(f, "replaced tx doesn't commit to the fascia"),
+            Self::CommitmentChanged => write!(f, "commitment output was changed"),
+            Self::InputsChanged => write!(f, "inputs or their order were changed"),
+            Self::Decode(e) => write!(f, "invalid tx: {e}"),
+            Self::Stock(e) => write!(f, "stock error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RbfError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockError {
+    /// Block or header can't be deserialized.
+    InvalidData(String),
+    /// Height 0 is the one of the genesis block, which has no witness.
+    InvalidHeight,
+    /// Block timestamp is before the one of the bitcoin genesis block.
+    InvalidTimestamp(i64),
+}
+
+impl std::fmt::Display for BlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidData(e) => write!(f, "invalid block data: {e}"),
+            Self::InvalidHeight => write!(f, "invalid block height 0"),
+            Self::InvalidTimestamp(time) => write!(f, "invalid block timestamp {time}"),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/// Stock operation failed, e.g. on a persistence error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StockFailure(pub String);
+
+impl std::fmt::Display for StockFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "stock operation failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for StockFailure {}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(std::io::Error),
+    /// Data is not a backup archive.
+    NotBackup,
+    /// Archive was written by a newer version.
+    UnsupportedVersion(u8),
+    /// Archive is corrupted.
+    ChecksumMismatch,
+    /// Archive is encrypted, but no passphrase was given.
+    PassphraseRequired,
+    /// Archive can't be decrypted with the given passphrase.
+    WrongPassphrase,
+    /// Archive content can't be decoded.
+    InvalidData(String),
+    /// Contract in the archive doesn't pass validation.
+    InvalidContract(rgbstd::ContractId, String),
+    /// Stock failed to export or import data, e.g. on a persistence error.
+    Stock(String),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::NotBackup => write!(f, "data is not a stock backup"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported backup version {version}")
+            }
+            Self::ChecksumMismatch => write!(f, "backup checksum mismatch"),
+            Self::PassphraseRequired => write!(f, "backup is encrypted, a passphrase is required"),
+            Self::WrongPassphrase => write!(f, "wrong backup passphrase"),
+            Self::InvalidData(e) => write!(f, "invalid backup data: {e}"),
+            Self::InvalidContract(contract_id, e) => {
+                write!(f, "contract {contract_id} in the backup is invalid: {e}")
+            }
+            Self::Stock(e) => write!(f, "stock error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<std::io::Error> for BackupError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AmountError {
+    /// Amount is not a decimal number optionally followed by a ticker.
+    InvalidFormat,
+    /// Precision is above the maximum of 18 decimals.
+    InvalidPrecision(u8),
+    /// Amount has more decimals than the precision allows.
+    TooManyDecimals(u8),
+    /// Amount doesn't fit into 64 bits once scaled to atomic units.
+    Overflow,
+    /// Ticker differs from the one of the contract.
+    TickerMismatch { expected: String, found: String },
+}
+
+impl std::fmt::Display for AmountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFormat => write!(f, "invalid amount format"),
+            Self::InvalidPrecision(precision) => write!(f, "invalid precision {precision}"),
+            Self::TooManyDecimals(precision) => {
+                write!(f, "amount has more than {precision} decimals")
+            }
+            Self::Overflow => write!(f, "amount overflow"),
+            Self::TickerMismatch { expected, found } => {
+                write!(f, "expected ticker {expected}, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+/// Invalid value of an issue request field, e.g. `allocations[1].amount`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum IssueError {
+    /// Request can't be deserialized.
+    Parse(String),
+    /// Request has invalid fields.
+    Invalid(Vec<FieldError>),
+    /// Media attachment can't be read.
+    Media(std::io::Error),
+}
+
+impl std::fmt::Display for IssueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "invalid issue request: {e}"),
+            Self::Invalid(errors) => {
+                write!(f, "invalid issue request")?;
+                for e in errors {
+                    write!(f, "; {e}")?;
+                }
+                Ok(())
+            }
+            Self::Media(e) => write!(f, "can't read media attachment: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for IssueError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkError {
+    /// Network name is not known.
+    Unknown(String),
+    /// Operation is not available on the network, e.g. SPV on Liquid.
+    Unsupported(Network),
+    /// Contract, or the transfer of it, was issued for another network.
+    ContractMismatch { contract_id: ContractId, network: Network },
+    /// Invoice beneficiary is on another network.
+    InvoiceMismatch { expected: Network, found: ChainNet },
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown(network) => write!(f, "unknown network `{network}`"),
+            Self::Unsupported(network) => write!(f, "operation is not supported on {network}"),
+            Self::ContractMismatch { contract_id, network } => {
+                write!(f, "contract {contract_id} was not issued for {network}")
+            }
+            Self::InvoiceMismatch { expected, found } => {
+                write!(f, "invoice is for {found} network instead of {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+#[derive(Debug)]
+pub enum ValidationError {
+    Network(NetworkError),
+    /// Consignment doesn't pass validation.
+    Invalid(Status),
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "{e}"),
+            Self::Invalid(status) => write!(f, "invalid consignment: {status}"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<NetworkError> for ValidationError {
+    fn from(e: NetworkError) -> Self {
+        Self::Network(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConsignmentError {
+    Io(std::io::Error),
+    /// Data is not a strict-encoded consignment of the expected type.
+    InvalidData(String),
+    /// Text is not an ASCII-armored consignment.
+    InvalidArmor(String),
+    /// Armor has no checksum header.
+    MissingChecksum,
+    /// Armored data doesn't match its checksum.
+    ChecksumMismatch,
+    /// Armor header is missing or doesn't match the consignment.
+    HeaderMismatch(String),
+    /// Consignment is for another network or doesn't pass validation.
+    Invalid(ValidationError),
+}
+
+impl std::fmt::Display for ConsignmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::InvalidData(e) => write!(f, "invalid consignment data: {e}"),
+            Self::InvalidArmor(e) => write!(f, "invalid consignment armor: {e}"),
+            Self::MissingChecksum => write!(f, "consignment armor has no checksum"),
+            Self::ChecksumMismatch => write!(f, "consignment armor checksum mismatch"),
+            Self::HeaderMismatch(title) => {
+                write!(f, "consignment armor header `{title}` is missing or doesn't match")
+            }
+            Self::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConsignmentError {}
+
+impl From<std::io::Error> for ConsignmentError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ValidationError> for ConsignmentError {
+    fn from(e: ValidationError) -> Self {
+        Self::Invalid(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum WalletError {
+    Network(NetworkError),
+    /// Contract is not known to the stock.
+    UnknownContract(ContractId),
+    /// Owned outpoints don't hold enough of the contract state.
+    InsufficientFunds { contract_id: ContractId, needed: u64, available: u64 },
+    /// Witness isn't a bitcoin tx or its commitment isn't an opret one.
+    Unsupported(String),
+    /// State transitions couldn't be composed.
+    Compose(String),
+    /// Stock failed to consume the fascia or the transfer.
+    Stock(String),
+}
+
+impl std::fmt::Display for WalletError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "{e}"),
+            Self::UnknownContract(contract_id) => write!(f, "unknown contract {contract_id}"),
+            Self::InsufficientFunds { contract_id, needed, available } => write!(
+                f,
+                "insufficient funds for contract {contract_id}: {needed} needed, {available} available"
+            ),
+            Self::Unsupported(e) => write!(f, "unsupported witness: {e}"),
+            Self::Compose(e) => write!(f, "failed to compose transitions: {e}"),
+            Self::Stock(e) => write!(f, "stock error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+impl From<NetworkError> for WalletError {
+    fn from(e: NetworkError) -> Self {
+        Self::Network(e)
+    }
+}
+
+impl From<StockFailure> for WalletError {
+    fn from(e: StockFailure) -> Self {
+        Self::Stock(e.0)
+    }
+}
+
+impl From<TransferError> for WalletError {
+    fn from(e: TransferError) -> Self {
+        match e {
+            TransferError::Network(e) => Self::Network(e),
+            TransferError::Stock(e) => Self::Compose(e),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvoiceError {
+    Network(NetworkError),
+    /// Amount doesn't fit the precision or ticker of the contract.
+    Amount(AmountError),
+}
+
+impl std::fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "{e}"),
+            Self::Amount(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for InvoiceError {}
+
+impl From<NetworkError> for InvoiceError {
+    fn from(e: NetworkError) -> Self {
+        Self::Network(e)
+    }
+}
+
+impl From<AmountError> for InvoiceError {
+    fn from(e: AmountError) -> Self {
+        Self::Amount(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferError {
+    Network(NetworkError),
+    /// Stock failed to compose the transitions or to consign the contract,
+    /// e.g. when the inputs don't hold enough state.
+    Stock(String),
+}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "{e}"),
+            Self::Stock(e) => write!(f, "stock error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+impl From<NetworkError> for TransferError {
+    fn from(e: NetworkError) -> Self {
+        Self::Network(e)
+    }
+}
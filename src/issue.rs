@@ -0,0 +1,222 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256, Hash};
+use bp::dbc::Method;
+use rgbstd::containers::ValidContract;
+use rgbstd::stl::{
+    Attachment, ContractTerms, Details, MediaRegName, MediaType, Name, RicardianContract, Ticker,
+};
+use rgbstd::{Identity, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::detail;
+use crate::error::{FieldError, IssueError};
+use crate::types::{Network, MAX_PRECISION};
+
+/// Token issuance parameters, e.g. loaded from a config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IssueRequest {
+    pub issuer: String,
+    pub ticker: String,
+    pub name: String,
+    #[serde(default)]
+    pub details: Option<String>,
+    pub precision: u8,
+    /// Ricardian contract text committed in genesis.
+    #[serde(default)]
+    pub terms: Option<String>,
+    #[serde(default)]
+    pub media: Option<MediaRequest>,
+    pub allocations: Vec<AllocationRequest>,
+    pub network: Network,
+}
+
+/// File attached to the contract terms, only its digest goes into genesis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MediaRequest {
+    pub path: PathBuf,
+    pub mime: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AllocationRequest {
+    #[serde(default = "default_close_method")]
+    pub close_method: String,
+    pub txid: String,
+    pub vout: u32,
+    pub amount: u64,
+}
+
+fn default_close_method() -> String {
+    Method::OpretFirst.to_string()
+}
+
+pub(crate) fn parse_media_type(mime: &str) -> Result<MediaType, String> {
+    let (ty, subtype) = mime
+        .split_once('/')
+        .ok_or_else(|| format!("media type `{mime}` must be in `type/subtype` form"))?;
+    let ty = MediaRegName::from_str(ty).map_err(|e| e.to_string())?;
+    let subtype = match subtype {
+        "*" => None,
+        subtype => Some(MediaRegName::from_str(subtype).map_err(|e| e.to_string())?),
+    };
+    Ok(MediaType {
+        ty,
+        subtype,
+        charset: None,
+    })
+}
+
+pub(crate) fn attachment(mime: MediaType, file: &[u8]) -> Attachment {
+    Attachment {
+        ty: mime,
+        digest: sha256::Hash::hash(file).to_byte_array().into(),
+    }
+}
+
+/// Builds the terms committed in genesis from the Ricardian contract text and
+/// the media file with its MIME type.
+pub(crate) fn contract_terms(
+    text: &str,
+    media: Option<(&str, &[u8])>,
+) -> Result<ContractTerms, IssueError> {
+    let mut errors = vec![];
+    let text = RicardianContract::from_str(text).map_err(|e| FieldError {
+        field: "terms".to_string(),
+        message: e.to_string(),
+    });
+    let media = media
+        .map(|(mime, file)| match parse_media_type(mime) {
+            Ok(mime) => Ok(attachment(mime, file)),
+            Err(message) => Err(FieldError {
+                field: "media.mime".to_string(),
+                message,
+            }),
+        })
+        .transpose();
+
+    match (text, media) {
+        (Ok(text), Ok(media)) => Ok(ContractTerms { text, media }),
+        (text, media) => {
+            errors.extend(text.err());
+            errors.extend(media.err());
+            Err(IssueError::Invalid(errors))
+        }
+    }
+}
+
+impl IssueRequest {
+    pub fn from_json(s: &str) -> Result<Self, IssueError> {
+        serde_json::from_str(s).map_err(|e| IssueError::Parse(e.to_string()))
+    }
+
+    pub fn from_yaml(s: &str) -> Result<Self, IssueError> {
+        serde_yaml::from_str(s).map_err(|e| IssueError::Parse(e.to_string()))
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, IssueError> {
+        toml::from_str(s).map_err(|e| IssueError::Parse(e.to_string()))
+    }
+
+    /// Checks every field, reporting all the invalid ones at once.
+    pub fn validate(&self) -> Result<(), IssueError> {
+        let mut errors = vec![];
+        let mut check = |field: &str, result: Result<(), String>| {
+            if let Err(message) = result {
+                errors.push(FieldError {
+                    field: field.to_string(),
+                    message,
+                });
+            }
+        };
+
+        check(
+            "issuer",
+            Identity::from_str(&self.issuer).map(|_| ()).map_err(|e| e.to_string()),
+        );
+        check("ticker", Ticker::from_str(&self.ticker).map(|_| ()).map_err(|e| e.to_string()));
+        check("name", Name::from_str(&self.name).map(|_| ()).map_err(|e| e.to_string()));
+        if let Some(details) = &self.details {
+            check("details", Details::from_str(details).map(|_| ()).map_err(|e| e.to_string()));
+        }
+        if self.precision > MAX_PRECISION {
+            check("precision", Err(format!("must be at most {MAX_PRECISION}")));
+        }
+        if let Some(terms) = &self.terms {
+            check(
+                "terms",
+                RicardianContract::from_str(terms).map(|_| ()).map_err(|e| e.to_string()),
+            );
+        }
+        if let Some(media) = &self.media {
+            check("media.mime", parse_media_type(&media.mime).map(|_| ()));
+            if !media.path.is_file() {
+                check("media.path", Err(format!("{} is not a file", media.path.display())));
+            }
+        }
+
+        if self.allocations.is_empty() {
+            check("allocations", Err("at least one allocation is required".to_string()));
+        }
+        let mut supply = Some(0u64);
+        for (i, allocation) in self.allocations.iter().enumerate() {
+            let field = |name: &str| format!("allocations[{i}].{name}");
+            let close_method = match Method::from_str(&allocation.close_method) {
+                Ok(Method::OpretFirst) => Ok(()),
+                Ok(method) => Err(format!("{method} is not supported")),
+                Err(e) => Err(e.to_string()),
+            };
+            check(&field("close_method"), close_method);
+            check(
+                &field("txid"),
+                Txid::from_str(&allocation.txid).map(|_| ()).map_err(|e| e.to_string()),
+            );
+            if allocation.amount == 0 {
+                check(&field("amount"), Err("must be positive".to_string()));
+            }
+            supply = supply.and_then(|supply| supply.checked_add(allocation.amount));
+        }
+        if supply.is_none() {
+            check("allocations", Err("total supply overflows 64 bits".to_string()));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(IssueError::Invalid(errors))
+        }
+    }
+
+    pub(crate) fn issue(&self) -> Result<ValidContract, IssueError> {
+        self.validate()?;
+
+        let file = match &self.media {
+            None => None,
+            Some(media) => Some(std::fs::read(&media.path).map_err(IssueError::Media)?),
+        };
+        let media = self
+            .media
+            .as_ref()
+            .zip(file.as_deref())
+            .map(|(media, file)| (media.mime.as_str(), file));
+        let terms = contract_terms(self.terms.as_deref().unwrap_or_default(), media)?;
+        let allocations = self.allocations.iter().map(|a| {
+            (format!("{}:{}:{}", a.close_method.to_lowercase(), a.txid, a.vout), a.amount)
+        });
+
+        Ok(detail::rgb_issue(
+            &self.issuer,
+            &self.ticker,
+            &self.name,
+            self.details.as_deref(),
+            self.precision,
+            terms,
+            allocations,
+            self.network,
+        ))
+    }
+}
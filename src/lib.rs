@@ -8,8 +8,14 @@ mod detail;
 mod types;
 mod resolvers;
 mod error;
+mod store;
+mod backup;
+mod wallet;
+mod issue;
+mod consignment;
 
 #[cfg(test)]
+#[allow(clippy::let_and_return, clippy::clone_on_copy)]
 mod tests;
 
 pub use rgbstd;
@@ -20,11 +26,26 @@ pub use strict_encoding;
 
 pub mod prelude {
     pub use crate::types::{
-        Beneficiary, ContractId, Outpoint, RgbAssignments, TransitionInfo, Txid,
+        AllocatedState, Allocation, BalanceChange, Beneficiary, ContractId, ContractInfo,
+        HistoryAllocation, HistoryEntry, Media, Network, Opout, Outpoint, RgbAmount, RgbAssignments,
+        Terms, TransitionInfo, Txid, WitnessUpdateReport,
     };
 
     pub use crate::api::*;
-    pub use crate::resolvers::{LnResolver, LocalResolver, FasciaResolver, OnlineResolver};
+    pub use crate::error::{
+        AmountError, BackupError, BlockError, ConsignmentError, FieldError, InvoiceError,
+        IssueError, NetworkError, RbfError, StockFailure, TransferError, ValidationError,
+        WalletError,
+    };
+    pub use crate::issue::{AllocationRequest, IssueRequest, MediaRequest};
+    pub use crate::resolvers::{
+        AsyncOnlineResolver, BitcoindResolver, CachingResolver, ChainResolver, ElectrumResolver,
+        FasciaResolver, LnResolver, LocalResolver, OnlineResolver, OnlineResolverBuilder,
+        PrefetchedResolver,
+    };
+    pub use crate::resolvers::{HeaderChain, SpvError};
+    pub use crate::wallet::RgbWallet;
+    pub use crate::store::{SqliteError, SqliteIndex, SqliteStash, SqliteState, SqliteStock};
     pub use strict_encoding::{StrictDeserialize, StrictSerialize};
     pub use rgbstd::{
         persistence::Stock,
@@ -1,16 +1,38 @@
+mod async_online;
+mod bitcoind;
+mod electrum;
+mod spv;
+
+pub use async_online::{AsyncOnlineResolver, PrefetchedResolver};
+pub use bitcoind::BitcoindResolver;
+pub use electrum::ElectrumResolver;
+pub use spv::{HeaderChain, SpvError};
+
 use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
 use rgbstd::{
-    containers::Consignment, validation::{
+    containers::{Consignment, Fascia, ToWitnessId}, validation::{
         ResolveWitness,
         WitnessResolverError,
     }, vm::{
         WitnessOrd, WitnessPos, XWitnessTx
     }, XChain, XWitnessId
 };
-use bp::{ConsensusDecode, ConsensusEncode, Tx};
+use bp::{ConsensusDecode, ConsensusDecodeError, ConsensusEncode, Outpoint, Tx};
 use bp::Txid;
 
+use crate::error::BlockError;
+use crate::store;
+use crate::types::Network;
+use crate::ToRaw;
+
 use backon::{
     BlockingRetryable,
     ExponentialBuilder,
@@ -86,7 +108,7 @@ impl ResolveWitness for LnResolver {
             return Ok(XChain::Bitcoin(tx.clone()));
         }
 
-        return Err(WitnessResolverError::Unknown(witness_id));
+        Err(WitnessResolverError::Unknown(witness_id))
     }
 
     fn resolve_pub_witness_ord(
@@ -109,15 +131,20 @@ impl ResolveWitness for LnResolver {
             return Ok(WitnessOrd::Archived)
         }
 
-        return Err(WitnessResolverError::Unknown(witness_id));
+        Err(WitnessResolverError::Unknown(witness_id))
     }
 
 }
 
 
+/// Offline resolver, knowing only the witnesses it has been given.
+///
+/// Witnesses are tentative unless their block is added too, either as a raw
+/// block or as a header along with the txids it contains.
 #[derive(Default, Debug)]
 pub struct LocalResolver {
     terminal_txes: HashMap<Txid, Tx>,
+    positions: HashMap<Txid, WitnessPos>,
 }
 
 impl LocalResolver {
@@ -125,6 +152,54 @@ impl LocalResolver {
         Self::default()
     }
 
+    /// Shorthand for `new` followed by `add_terminals`.
+    pub fn with_consignment<const TYPE: bool>(consignment: &Consignment<TYPE>) -> Self {
+        let mut resolver = Self::new();
+        resolver.add_terminals(consignment);
+        resolver
+    }
+
+    /// Makes all the txs of the block mined at `height`.
+    ///
+    /// Fails on invalid data, at height 0, or if the block timestamp is
+    /// before the one of the bitcoin genesis block.
+    pub fn add_block(
+        &mut self,
+        height: u32,
+        consensus_serialized_block: &[u8],
+    ) -> Result<(), BlockError> {
+        let block: bitcoin::Block = bitcoin::consensus::deserialize(consensus_serialized_block)
+            .map_err(|e| BlockError::InvalidData(e.to_string()))?;
+        let witness_pos = block_position(height, block.header.time)?;
+
+        for tx in &block.txdata {
+            let tx = from_bitcoin_tx(tx);
+            self.positions.insert(tx.txid(), witness_pos);
+            self.terminal_txes.insert(tx.txid(), tx);
+        }
+        Ok(())
+    }
+
+    /// Same as `add_block`, but with the txids of the block instead of the full txs.
+    pub fn add_block_header(
+        &mut self,
+        height: u32,
+        consensus_serialized_header: &[u8],
+        txids: impl IntoIterator<Item = impl Into<crate::types::Txid>>,
+    ) -> Result<(), BlockError> {
+        let header = bp::BlockHeader::consensus_deserialize(consensus_serialized_header)
+            .map_err(|e| BlockError::InvalidData(e.to_string()))?;
+        let witness_pos = block_position(height, header.time)?;
+
+        self.positions.extend(
+            txids
+                .into_iter()
+                .map(|txid| (txid.into().to_raw(), witness_pos)),
+        );
+        Ok(())
+    }
+
+    /// Adds the witnesses of every bundle in the consignment, not only the terminal ones.
     pub fn add_terminals<const TYPE: bool>(&mut self, consignment: &Consignment<TYPE>) {
         self.terminal_txes.extend(
             consignment
@@ -156,7 +231,7 @@ impl ResolveWitness for LocalResolver {
             return Ok(XWitnessTx::Bitcoin(tx.clone()));
         }
 
-        return Err(WitnessResolverError::Unknown(witness_id));
+        Err(WitnessResolverError::Unknown(witness_id))
     }
 
     fn resolve_pub_witness_ord(
@@ -170,30 +245,321 @@ impl ResolveWitness for LocalResolver {
             ));
         };
 
+        if let Some(witness_pos) = self.positions.get(&txid) {
+            return Ok(WitnessOrd::Mined(*witness_pos));
+        }
+
         if self.terminal_txes.contains_key(&txid) {
             return Ok(WitnessOrd::Tentative);
         }
 
-        return Err(WitnessResolverError::Unknown(witness_id));
+        Err(WitnessResolverError::Unknown(witness_id))
     }
 }
 
 
+/// Resolver backed by an Esplora server.
+///
+/// A witness unknown to the server is told apart as archived, when one of its
+/// inputs was spent by another tx, or tentative, when it can still be
+/// broadcast. This needs the witness tx itself, so it must have been given to
+/// `add_witness_tx` or `add_terminals`, or resolved before. Otherwise the
+/// witness is reported as unknown.
 #[derive(Debug)]
 pub struct OnlineResolver {
-    // TODO
-    #[allow(unused)]
     esplora_url: String,
     client: esplora_client::BlockingClient,
+    backoff: ExponentialBuilder,
+
+    // Witnesses we have seen, used to tell whether a witness unknown to the
+    // server was double spent or simply not broadcast yet.
+    known_txs: Mutex<HashMap<Txid, Tx>>,
+
+    // Set in SPV mode, where mined witnesses are checked against it.
+    header_chain: Option<Mutex<HeaderChain>>,
+
+    chain: ChainCheck,
 }
 
 impl OnlineResolver {
     pub fn new(esplora_url: &str) -> Self {
-        let builder = esplora_client::Builder::new(esplora_url);
+        Self::builder(esplora_url).build()
+    }
+
+    pub fn builder(esplora_url: &str) -> OnlineResolverBuilder {
+        OnlineResolverBuilder::new(esplora_url)
+    }
+
+    pub fn esplora_url(&self) -> &str {
+        &self.esplora_url
+    }
+
+    /// Headers synced so far in SPV mode, e.g. to be persisted for the next run.
+    pub fn header_chain(&self) -> Option<HeaderChain> {
+        self.header_chain
+            .as_ref()
+            .map(|chain| chain.lock().unwrap().clone())
+    }
+
+    /// Makes a witness which may be unknown to the server, e.g. not yet
+    /// broadcast, resolvable as tentative.
+    pub fn add_witness_tx(
+        &mut self,
+        consensus_serialized_tx: &[u8],
+    ) -> Result<(), ConsensusDecodeError> {
+        let tx = Tx::consensus_deserialize(consensus_serialized_tx)?;
+        self.known_txs.get_mut().unwrap().insert(tx.txid(), tx);
+        Ok(())
+    }
+
+    /// Same as `add_witness_tx`, for the witnesses of every bundle in the consignment.
+    pub fn add_terminals<const TYPE: bool>(&mut self, consignment: &Consignment<TYPE>) {
+        self.known_txs.get_mut().unwrap().extend(
+            consignment
+                .bundles
+                .iter()
+                .filter_map(|bw| bw.pub_witness.maybe_map_ref(|w| w.tx().cloned()))
+                .filter_map(|tx| match tx {
+                    XChain::Bitcoin(tx) => Some(tx),
+                    XChain::Liquid(_) | XChain::Other(_) => None,
+                })
+                .map(|tx| (tx.txid(), tx)),
+        );
+    }
+
+    // Checks the witness is included in a block of the local header chain,
+    // and takes the block timestamp from there rather than from the server.
+    fn verify_mined(
+        &self,
+        witness_id: XWitnessId,
+        txid: bitcoin::Txid,
+        header_chain: &Mutex<HeaderChain>,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let invalid_data = |e: String| WitnessResolverError::Other(witness_id, e);
+
+        let proof = self
+            .retry(witness_id, || self.client.get_merkle_proof(&txid))?
+            .ok_or_else(|| invalid_data("missing merkle proof for a mined witness".to_string()))?;
+        let height = proof.block_height;
+
+        let mut header_chain = header_chain.lock().unwrap();
+        if header_chain.header_at(height).is_none() {
+            self.sync_headers(witness_id, &mut header_chain)?;
+        }
+        let merkle_branch = || proof.merkle.iter().map(|node| node.to_raw_hash());
+        // Our header at that height may have been reorged out since it was synced.
+        if let Err(SpvError::MerkleRootMismatch { .. }) =
+            header_chain.verify_inclusion(txid, height, merkle_branch(), proof.pos)
+        {
+            self.sync_headers(witness_id, &mut header_chain)?;
+        }
+        let header = header_chain
+            .verify_inclusion(txid, height, merkle_branch(), proof.pos)
+            .map_err(|e| invalid_data(e.to_string()))?;
+
+        let height = NonZeroU32::new(height)
+            .ok_or_else(|| invalid_data("Invalid block height".to_string()))?;
+        let pos = WitnessPos::bitcoin(height, header.time as i64)
+            .ok_or_else(|| invalid_data("Invalid block timestamp".to_string()))?;
+        Ok(WitnessOrd::Mined(pos))
+    }
+
+    // Brings the header chain to the server tip, first dropping the headers
+    // which have been reorged out.
+    fn sync_headers(
+        &self,
+        witness_id: XWitnessId,
+        header_chain: &mut HeaderChain,
+    ) -> Result<(), WitnessResolverError> {
+        let tip_height = self.retry(witness_id, || self.client.get_height())?;
+        self.rewind_to_fork_point(witness_id, header_chain, tip_height)?;
+        while header_chain.tip_height() < tip_height {
+            let height = header_chain.tip_height() + 1;
+            let hash = self.retry(witness_id, || self.client.get_block_hash(height))?;
+            let header = self.retry(witness_id, || self.client.get_header_by_hash(&hash))?;
+            match header_chain.push(header) {
+                Ok(()) => {}
+                // Reorged while syncing, the server must have a new fork point
+                // below our tip, otherwise it's serving inconsistent headers.
+                Err(e @ SpvError::Disconnected { .. }) => {
+                    self.rewind_to_fork_point(witness_id, header_chain, height - 1)?;
+                    if header_chain.tip_height() == height - 1 {
+                        return Err(WitnessResolverError::Other(witness_id, e.to_string()));
+                    }
+                }
+                Err(e) => return Err(WitnessResolverError::Other(witness_id, e.to_string())),
+            }
+        }
+        Ok(())
+    }
 
+    // Rewinds the header chain to the highest header, at most `height`, which
+    // is still in the chain of the server.
+    fn rewind_to_fork_point(
+        &self,
+        witness_id: XWitnessId,
+        header_chain: &mut HeaderChain,
+        height: u32,
+    ) -> Result<(), WitnessResolverError> {
+        let mut height = height.min(header_chain.tip_height());
+        if height < header_chain.checkpoint_height() {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                "chain of the server is below the checkpoint".to_string(),
+            ));
+        }
+        loop {
+            let hash = self.retry(witness_id, || self.client.get_block_hash(height))?;
+            let Some(header) = header_chain.header_at(height) else {
+                return Err(WitnessResolverError::Other(
+                    witness_id,
+                    format!("no header at height {height} in the header chain"),
+                ));
+            };
+            if header.block_hash() == hash {
+                header_chain.rewind(height);
+                return Ok(());
+            }
+            if height == header_chain.checkpoint_height() {
+                return Err(WitnessResolverError::Other(
+                    witness_id,
+                    "chain of the server doesn't include the checkpoint".to_string(),
+                ));
+            }
+            height -= 1;
+        }
+    }
+
+    // Inputs of a witness unknown to the server are only known if it was
+    // given to `add_witness_tx` or `add_terminals`, or resolved before,
+    // otherwise it stays unknown.
+    fn resolve_unknown_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+        txid: Txid,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let Some(tx) = self.known_txs.lock().unwrap().get(&txid).cloned() else {
+            return Err(WitnessResolverError::Unknown(witness_id));
+        };
+
+        let spenders = spent_outputs(&tx).map(|prev_output| {
+            let prev_txid = to_esplora_txid(prev_output.txid);
+            let vout = prev_output.vout.into_u32() as u64;
+            self.retry(witness_id, || self.client.get_output_status(&prev_txid, vout))
+                .map(esplora_spender)
+        });
+        unknown_witness_ord(txid, spenders)
+    }
+
+    fn retry<T>(
+        &self,
+        witness_id: XWitnessId,
+        op: impl FnMut() -> Result<T, esplora_client::Error>,
+    ) -> Result<T, WitnessResolverError> {
+        op.retry(self.backoff)
+            .when(is_retryable)
+            .call()
+            .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))
+    }
+
+    fn check_chain(&self, witness_id: XWitnessId) -> Result<(), WitnessResolverError> {
+        self.chain
+            .ensure(witness_id, || self.retry(witness_id, || self.client.get_block_hash(0)))
+    }
+}
+
+/// Builder for `OnlineResolver`.
+///
+/// Failed requests are retried with an exponential backoff, except for the
+/// definitive answers from the server, e.g. a tx which is not found.
+///
+/// TLS can't be configured: the underlying HTTP client verifies `https` servers
+/// against the bundled Mozilla root certificates only, and has no option for a
+/// custom CA or for accepting invalid certificates. Such servers must be
+/// reached over plain `http`, e.g. through a local TLS-terminating proxy.
+#[derive(Debug)]
+pub struct OnlineResolverBuilder {
+    esplora: esplora_client::Builder,
+    backoff: ExponentialBuilder,
+    header_chain: Option<HeaderChain>,
+    network: Option<Network>,
+}
+
+impl OnlineResolverBuilder {
+    pub fn new(esplora_url: &str) -> Self {
         Self {
-            esplora_url: esplora_url.to_string(),
-            client: builder.build_blocking(),
+            // Retries are handled by the resolver, so that they are classified
+            // the same way for every kind of failure.
+            esplora: esplora_client::Builder::new(esplora_url).max_retries(0),
+            backoff: default_backoff(),
+            header_chain: None,
+            network: None,
+        }
+    }
+
+    /// Refuses to resolve anything if the server is on another network,
+    /// which is checked against its genesis block on the first request.
+    /// Liquid servers are always refused.
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.backoff = self.backoff.with_max_times(max_retries);
+        self
+    }
+
+    /// Delay before the first retry, doubled on each subsequent one.
+    pub fn min_delay(mut self, delay: Duration) -> Self {
+        self.backoff = self.backoff.with_min_delay(delay);
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.backoff = self.backoff.with_max_delay(delay);
+        self
+    }
+
+    /// Timeout of each request.
+    ///
+    /// The HTTP client only supports whole seconds, so the timeout is rounded
+    /// up to the next second, e.g. 1.5s becomes 2s and 100ms becomes 1s.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+        self.esplora = self.esplora.timeout(secs.max(1));
+        self
+    }
+
+    /// Proxy formatted as `<protocol>://<user>:<password>@host:<port>`,
+    /// SOCKS5 proxies like Tor are supported.
+    pub fn proxy(mut self, proxy: &str) -> Self {
+        self.esplora = self.esplora.proxy(proxy);
+        self
+    }
+
+    /// HTTP header to be sent with every request, e.g. for authentication.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.esplora = self.esplora.header(key, value);
+        self
+    }
+
+    /// Enables SPV mode: mined witnesses must be proven to be included in a
+    /// block of `header_chain`, which is synced from the server on demand.
+    /// The server can then hide a confirmation, but not fake one.
+    pub fn spv(mut self, header_chain: HeaderChain) -> Self {
+        self.header_chain = Some(header_chain);
+        self
+    }
+
+    pub fn build(self) -> OnlineResolver {
+        OnlineResolver {
+            esplora_url: self.esplora.base_url.clone(),
+            client: self.esplora.build_blocking(),
+            backoff: self.backoff,
+            known_txs: Default::default(),
+            header_chain: self.header_chain.map(Mutex::new),
+            chain: self.network.map(ChainCheck::new).unwrap_or_default(),
         }
     }
 }
@@ -209,29 +575,22 @@ impl ResolveWitness for OnlineResolver {
                 format!("{} is not supported as layer 1 network", witness_id.layer1()),
             ));
         };
+        self.check_chain(witness_id)?;
 
-        let txid = txid
-            .to_string()
-            .parse()
-            .unwrap();
-
-        let op = || self
-            .client
-            .get_tx(&txid)
-            .map(|tx_opt| {
-                tx_opt.map(|tx| {
-                    use bitcoin::consensus::Encodable;
-
-                    let mut buf = Vec::new();
-                    tx.consensus_encode(&mut buf).unwrap();
-                    Tx::consensus_deserialize(&buf).unwrap()
-                })
-            })
-            .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))
-            .and_then(|r| r.ok_or(WitnessResolverError::Unknown(witness_id)))
-            .map(XChain::Bitcoin);
+        let txid = to_esplora_txid(txid);
 
-        op.retry(default_backoff()).call()
+        if let Some(tx) = self.retry(witness_id, || self.client.get_tx(&txid))? {
+            let tx = from_bitcoin_tx(&tx);
+            self.known_txs.lock().unwrap().insert(tx.txid(), tx.clone());
+            return Ok(XChain::Bitcoin(tx));
+        }
+
+        self.known_txs
+            .lock()
+            .unwrap()
+            .get(&to_bp_txid(txid))
+            .map(|tx| XChain::Bitcoin(tx.clone()))
+            .ok_or(WitnessResolverError::Unknown(witness_id))
     }
 
     fn resolve_pub_witness_ord(
@@ -244,61 +603,474 @@ impl ResolveWitness for OnlineResolver {
                 format!("{} is not supported as layer 1 network", witness_id.layer1()),
             ));
         };
-        let txid = txid
-            .to_string()
-            .parse()
-            .unwrap();
-
-        let op = || {
-            let tx_opt = self
-                .client
-                .get_tx(&txid)
-                .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))?;
-            if tx_opt.is_none() {
-                return Ok(WitnessOrd::Archived);
+        self.check_chain(witness_id)?;
+        let esplora_txid = to_esplora_txid(txid);
+
+        if self.retry(witness_id, || self.client.get_tx(&esplora_txid))?.is_none() {
+            return self.resolve_unknown_witness_ord(witness_id, txid);
+        }
+
+        let status = self.retry(witness_id, || self.client.get_tx_status(&esplora_txid))?;
+        let ord = esplora_status_to_ord(witness_id, &status)?;
+        match (ord, &self.header_chain) {
+            (WitnessOrd::Mined(_), Some(header_chain)) => {
+                self.verify_mined(witness_id, esplora_txid, header_chain)
+            }
+            _ => Ok(ord),
+        }
+    }
+}
+
+/// Resolver for the witness of a fascia being consumed.
+///
+/// The witness is reported as tentative, since it's usually not broadcast yet.
+/// Other witnesses are unknown to it.
+#[derive(Clone, Debug)]
+pub struct FasciaResolver {
+    witness_id: XWitnessId,
+    witness_tx: Option<Tx>,
+}
+
+impl FasciaResolver {
+    pub fn new(fascia: &Fascia) -> Self {
+        Self {
+            witness_id: fascia.witness.to_witness_id(),
+            witness_tx: match fascia.witness {
+                XChain::Bitcoin(ref pub_witness) => pub_witness.tx().cloned(),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl ResolveWitness for FasciaResolver {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        if witness_id != self.witness_id {
+            return Err(WitnessResolverError::Unknown(witness_id));
+        }
+        match self.witness_tx {
+            Some(ref tx) => Ok(XChain::Bitcoin(tx.clone())),
+            None => Err(WitnessResolverError::Other(
+                witness_id,
+                "fascia witness doesn't contain the tx".to_string(),
+            )),
+        }
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        if witness_id != self.witness_id {
+            return Err(WitnessResolverError::Unknown(witness_id));
+        }
+        Ok(WitnessOrd::Tentative)
+    }
+}
+
+/// Resolver trying several backends in order.
+///
+/// A backend reporting `WitnessResolverError::Unknown` is skipped in favor of
+/// the next one, any other error is returned as is.
+#[derive(Default)]
+pub struct ChainResolver<'a> {
+    resolvers: Vec<Box<dyn ResolveWitness + 'a>>,
+}
+
+impl<'a> ChainResolver<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_resolver(&mut self, resolver: impl ResolveWitness + 'a) {
+        self.resolvers.push(Box::new(resolver));
+    }
+
+    #[must_use]
+    pub fn with_resolver(mut self, resolver: impl ResolveWitness + 'a) -> Self {
+        self.add_resolver(resolver);
+        self
+    }
+}
+
+impl ResolveWitness for ChainResolver<'_> {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        for resolver in &self.resolvers {
+            match resolver.resolve_pub_witness(witness_id) {
+                Err(WitnessResolverError::Unknown(_)) => continue,
+                res => return res,
+            }
+        }
+
+        Err(WitnessResolverError::Unknown(witness_id))
+    }
+
+    // Asks every backend, since they may disagree on the witness status,
+    // e.g. a local one still seeing it as tentative while it's already mined.
+    // Preference is `Mined` > `Tentative` > `Archived`. A failing backend
+    // doesn't discard the answers of the others, its error is only returned
+    // when none of them knows the witness.
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let rank = |ord: &WitnessOrd| match ord {
+            WitnessOrd::Mined(_) => 2,
+            WitnessOrd::Tentative => 1,
+            WitnessOrd::Archived => 0,
+        };
+
+        let mut best: Option<WitnessOrd> = None;
+        let mut error = None;
+        for resolver in &self.resolvers {
+            match resolver.resolve_pub_witness_ord(witness_id) {
+                Ok(ord) => {
+                    // Keep the first one for ties, backends are in priority order.
+                    if best.is_none_or(|best| rank(&ord) > rank(&best)) {
+                        best = Some(ord);
+                    }
+                }
+                Err(WitnessResolverError::Unknown(_)) => continue,
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
             }
+        }
+
+        match (best, error) {
+            (Some(ord), _) => Ok(ord),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(WitnessResolverError::Unknown(witness_id)),
+        }
+    }
+}
+
+
+/// Resolver caching the results of another one.
+///
+/// Transactions are cached forever, while mined positions are only cached
+/// once they are buried under enough confirmations to not be reorged out.
+/// Tentative and archived witnesses are always resolved by the inner resolver.
+///
+/// Confirmations are counted from the chain tip given at construction, which
+/// is then raised to the height of every mined witness the inner resolver
+/// reports, or explicitly with `set_tip_height`.
+#[derive(Debug)]
+pub struct CachingResolver<R: ResolveWitness> {
+    inner: R,
+    cache_dir: Option<PathBuf>,
+    min_confirmations: u32,
+    tip_height: AtomicU32,
+
+    txs: Mutex<HashMap<Txid, Tx>>,
+    positions: Mutex<HashMap<Txid, WitnessPos>>,
+}
+
+impl<R: ResolveWitness> CachingResolver<R> {
+    const TX_DIR: &'static str = "txs";
+    const POS_DIR: &'static str = "positions";
+
+    /// In-memory cache, `tip_height` being the height of the current chain tip.
+    pub fn new(inner: R, tip_height: u32) -> Self {
+        Self {
+            inner,
+            cache_dir: None,
+            min_confirmations: 6,
+            tip_height: AtomicU32::new(tip_height),
+            txs: Default::default(),
+            positions: Default::default(),
+        }
+    }
+
+    /// Cache persisted in `cache_dir`, previously cached entries are loaded from it.
+    ///
+    /// Unreadable entries are deleted, they will simply be refetched.
+    pub fn with_cache_dir(
+        inner: R,
+        tip_height: u32,
+        cache_dir: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let cache_dir = cache_dir.as_ref();
+        fs::create_dir_all(cache_dir.join(Self::TX_DIR))?;
+        fs::create_dir_all(cache_dir.join(Self::POS_DIR))?;
 
-            let status = self.client.get_tx_status(&txid)
-                .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))?;
-            let ord = match status
-                .block_height
-                .and_then(|h| status.block_time.map(|t| (h, t)))
-            {
-                Some((h, t)) => {
-                    
-                    let h = std::num::NonZeroU32::new(h).ok_or_else(|| WitnessResolverError::Other(witness_id, "Invalid block height".to_string()))?;
-                    let pos = WitnessPos::bitcoin(h, t as i64)
-                        .ok_or_else(|| WitnessResolverError::Other(witness_id, "Invalid server data".to_string()))?;
-                    WitnessOrd::Mined(pos)
+        let mut txs = HashMap::new();
+        for entry in fs::read_dir(cache_dir.join(Self::TX_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                continue;
+            }
+            match fs::read(&path).ok().and_then(|tx| Tx::consensus_deserialize(tx).ok()) {
+                Some(tx) => {
+                    txs.insert(tx.txid(), tx);
+                }
+                None => {
+                    let _ = fs::remove_file(&path);
                 }
-                None => WitnessOrd::Tentative,
+            }
+        }
+
+        let mut positions = HashMap::new();
+        for entry in fs::read_dir(cache_dir.join(Self::POS_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                continue;
+            }
+            let parse = || -> Option<(Txid, WitnessPos)> {
+                let txid = path.file_name()?.to_str()?.parse().ok()?;
+                let content = fs::read_to_string(&path).ok()?;
+                let (height, timestamp) = content.trim().split_once(' ')?;
+                let height = NonZeroU32::new(height.parse().ok()?)?;
+                let pos = WitnessPos::bitcoin(height, timestamp.parse().ok()?)?;
+                Some((txid, pos))
             };
-            Ok(ord)
+            match parse() {
+                Some((txid, pos)) => {
+                    positions.insert(txid, pos);
+                }
+                None => {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+
+        Ok(Self {
+            cache_dir: Some(cache_dir.to_path_buf()),
+            txs: Mutex::new(txs),
+            positions: Mutex::new(positions),
+            ..Self::new(inner, tip_height)
+        })
+    }
+
+    /// Number of confirmations required to cache a mined position, 6 by default.
+    pub fn set_min_confirmations(&mut self, min_confirmations: u32) {
+        self.min_confirmations = min_confirmations.max(1);
+    }
+
+    /// Raises the chain tip, a lower height than the known one is ignored.
+    pub fn set_tip_height(&self, tip_height: u32) {
+        self.tip_height.fetch_max(tip_height, Ordering::Relaxed);
+    }
+
+    pub fn tip_height(&self) -> u32 {
+        self.tip_height.load(Ordering::Relaxed)
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    fn is_buried(&self, pos: &WitnessPos) -> bool {
+        self.tip_height()
+            .checked_sub(pos.height().get())
+            .is_some_and(|depth| depth + 1 >= self.min_confirmations)
+    }
+
+    fn persist(&self, dir: &str, txid: &Txid, content: &[u8]) {
+        let Some(cache_dir) = &self.cache_dir else {
+            return;
         };
-        op.retry(default_backoff()).call()
+        // Write and sync a temporary file first, so a crash never leaves a
+        // truncated entry.
+        let path = cache_dir.join(dir).join(txid.to_string());
+        let tmp_path = path.with_extension("tmp");
+        // The cache is best effort, a failed entry will simply be refetched next time.
+        let _ = fs::File::create(&tmp_path)
+            .and_then(|mut file| file.write_all(content).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .and_then(|_| store::sync_dir(&path));
     }
 }
 
-/// Unchecked fascia resolver
-pub struct FasciaResolver;
-
-impl ResolveWitness for FasciaResolver {
+impl<R: ResolveWitness> ResolveWitness for CachingResolver<R> {
     fn resolve_pub_witness(
         &self,
-        _: XWitnessId,
+        witness_id: XWitnessId,
     ) -> Result<XWitnessTx, WitnessResolverError> {
-        unreachable!()
+        let XWitnessId::Bitcoin(txid) = witness_id else {
+            return self.inner.resolve_pub_witness(witness_id);
+        };
+
+        if let Some(tx) = self.txs.lock().unwrap().get(&txid) {
+            return Ok(XChain::Bitcoin(tx.clone()));
+        }
+
+        let witness = self.inner.resolve_pub_witness(witness_id)?;
+        if let XChain::Bitcoin(ref tx) = witness {
+            if tx.txid() == txid {
+                self.persist(Self::TX_DIR, &txid, &tx.consensus_serialize());
+                self.txs.lock().unwrap().insert(txid, tx.clone());
+            }
+        }
+
+        Ok(witness)
     }
 
     fn resolve_pub_witness_ord(
         &self,
-        _witness_id: XWitnessId,
+        witness_id: XWitnessId,
     ) -> Result<WitnessOrd, WitnessResolverError> {
-        Ok(WitnessOrd::Tentative)
+        let XWitnessId::Bitcoin(txid) = witness_id else {
+            return self.inner.resolve_pub_witness_ord(witness_id);
+        };
+
+        if let Some(pos) = self.positions.lock().unwrap().get(&txid) {
+            return Ok(WitnessOrd::Mined(*pos));
+        }
+
+        let ord = self.inner.resolve_pub_witness_ord(witness_id)?;
+        if let WitnessOrd::Mined(pos) = ord {
+            // The block of a mined witness is part of the chain, so the tip is at least there.
+            self.set_tip_height(pos.height().get());
+            if self.is_buried(&pos) {
+                let content = format!("{} {}", pos.height(), pos.timestamp());
+                self.persist(Self::POS_DIR, &txid, content.as_bytes());
+                self.positions.lock().unwrap().insert(txid, pos);
+            }
+        }
+
+        Ok(ord)
     }
 }
 
 
+fn block_position(height: u32, time: u32) -> Result<WitnessPos, BlockError> {
+    let height = NonZeroU32::new(height).ok_or(BlockError::InvalidHeight)?;
+    WitnessPos::bitcoin(height, time as i64).ok_or(BlockError::InvalidTimestamp(time as i64))
+}
+
+fn to_esplora_txid(txid: Txid) -> bitcoin::Txid {
+    txid.to_string().parse().unwrap()
+}
+
+fn to_bp_txid(txid: bitcoin::Txid) -> Txid {
+    txid.to_string().parse().unwrap()
+}
+
+fn esplora_spender(output_status: Option<esplora_client::OutputStatus>) -> Option<Txid> {
+    output_status
+        .filter(|status| status.spent)
+        .and_then(|status| status.txid)
+        .map(to_bp_txid)
+}
+
+fn spent_outputs(tx: &Tx) -> impl Iterator<Item = Outpoint> + '_ {
+    tx.inputs
+        .iter()
+        .map(|input| input.prev_output)
+        .filter(|prev_output| !prev_output.is_coinbase())
+}
+
+// A witness unknown to the backend, or neither mined nor in its mempool, is
+// archived only if one of its inputs has been spent by another tx, otherwise
+// it can still be broadcast. `spenders` are the txs spending the
+// `spent_outputs` of the witness, as looked up by the backend.
+fn unknown_witness_ord<E>(
+    txid: Txid,
+    spenders: impl IntoIterator<Item = Result<Option<Txid>, E>>,
+) -> Result<WitnessOrd, E> {
+    for spender in spenders {
+        if spender?.is_some_and(|spender| spender != txid) {
+            return Ok(WitnessOrd::Archived);
+        }
+    }
+
+    Ok(WitnessOrd::Tentative)
+}
+
+fn from_bitcoin_tx(tx: &bitcoin::Transaction) -> Tx {
+    use bitcoin::consensus::Encodable;
+
+    let mut buf = Vec::new();
+    tx.consensus_encode(&mut buf).unwrap();
+    Tx::consensus_deserialize(&buf).unwrap()
+}
+
+fn esplora_status_to_ord(
+    witness_id: XWitnessId,
+    status: &esplora_client::TxStatus,
+) -> Result<WitnessOrd, WitnessResolverError> {
+    let ord = match status
+        .block_height
+        .and_then(|h| status.block_time.map(|t| (h, t)))
+    {
+        Some((h, t)) => {
+            let h = NonZeroU32::new(h).ok_or_else(|| WitnessResolverError::Other(witness_id, "Invalid block height".to_string()))?;
+            let pos = WitnessPos::bitcoin(h, t as i64)
+                .ok_or_else(|| WitnessResolverError::Other(witness_id, "Invalid server data".to_string()))?;
+            WitnessOrd::Mined(pos)
+        }
+        None => WitnessOrd::Tentative,
+    };
+    Ok(ord)
+}
+
 fn default_backoff() -> ExponentialBuilder {
     ExponentialBuilder::default()
+}
+
+// Network a backend must be on, checked against the genesis block it reports
+// before its first answer is trusted.
+#[derive(Debug, Default)]
+struct ChainCheck {
+    network: Option<Network>,
+    checked: AtomicBool,
+}
+
+impl ChainCheck {
+    fn new(network: Network) -> Self {
+        Self {
+            network: Some(network),
+            checked: AtomicBool::new(false),
+        }
+    }
+
+    // Whether the genesis block must still be asked to the backend.
+    fn is_pending(&self) -> bool {
+        self.network.is_some() && !self.checked.load(Ordering::Relaxed)
+    }
+
+    // A failure to get the genesis block is not remembered, so that the
+    // check is retried with the next request.
+    fn ensure(
+        &self,
+        witness_id: XWitnessId,
+        genesis_hash: impl FnOnce() -> Result<bitcoin::BlockHash, WitnessResolverError>,
+    ) -> Result<(), WitnessResolverError> {
+        let Some(network) = self.network else {
+            return Ok(());
+        };
+        if self.checked.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let params = bitcoin::Network::try_from(network)
+            .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))?;
+        let expected = bitcoin::constants::genesis_block(params).block_hash();
+        let found = genesis_hash()?;
+        if found != expected {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                format!("backend is not on {network}, its genesis block is {found}"),
+            ));
+        }
+        self.checked.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Only transport failures and server overload are worth retrying, any other
+// answer would be the same the next time.
+fn is_retryable(e: &esplora_client::Error) -> bool {
+    match e {
+        esplora_client::Error::Minreq(_) | esplora_client::Error::Reqwest(_) => true,
+        esplora_client::Error::HttpResponse { status, .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
 }
\ No newline at end of file
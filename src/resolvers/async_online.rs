@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::io;
+
+use backon::Retryable;
+use bp::{Tx, Txid};
+use futures::future::join_all;
+use rgbstd::{
+    containers::{Consignment, ToWitnessId},
+    validation::{ResolveWitness, WitnessResolverError},
+    vm::{WitnessOrd, XWitnessTx},
+    XChain, XWitnessId,
+};
+
+use super::{
+    default_backoff, esplora_spender, esplora_status_to_ord, from_bitcoin_tx, is_retryable,
+    spent_outputs, to_esplora_txid, unknown_witness_ord, ChainCheck,
+};
+use crate::types::Network;
+
+/// Async counterpart of `OnlineResolver`.
+///
+/// Since `ResolveWitness` is a blocking interface, it doesn't implement it
+/// directly. Instead, all the witnesses of a consignment are fetched upfront
+/// into a `PrefetchedResolver`, which can then be used for validation without
+/// blocking the executor.
+#[derive(Debug)]
+pub struct AsyncOnlineResolver {
+    client: esplora_client::AsyncClient,
+    chain: ChainCheck,
+}
+
+impl AsyncOnlineResolver {
+    /// Fails if the HTTP client can't be built, e.g. for an invalid URL.
+    pub fn new(esplora_url: &str) -> io::Result<Self> {
+        // Retries are handled by the resolver, same as `OnlineResolver`.
+        let builder = esplora_client::Builder::new(esplora_url).max_retries(0);
+
+        Ok(Self {
+            client: builder.build_async().map_err(io::Error::other)?,
+            chain: ChainCheck::default(),
+        })
+    }
+
+    /// Refuses to prefetch anything if the server is on another network,
+    /// which is checked against its genesis block on the first prefetch.
+    /// Liquid servers are always refused.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.chain = ChainCheck::new(network);
+        self
+    }
+
+    /// Fetches the witnesses of every bundle in the consignment concurrently.
+    pub async fn prefetch<const TYPE: bool>(
+        &self,
+        consignment: &Consignment<TYPE>,
+    ) -> Result<PrefetchedResolver, WitnessResolverError> {
+        let mut witnesses = HashMap::<Txid, Option<Tx>>::new();
+        for bw in &consignment.bundles {
+            let XChain::Bitcoin(ref pub_witness) = bw.pub_witness else {
+                let witness_id = bw.pub_witness.to_witness_id();
+                return Err(WitnessResolverError::Other(
+                    witness_id,
+                    format!(
+                        "{} is not supported as layer 1 network",
+                        witness_id.layer1()
+                    ),
+                ));
+            };
+            let tx = witnesses.entry(pub_witness.txid()).or_default();
+            if tx.is_none() {
+                *tx = pub_witness.tx().cloned();
+            }
+        }
+
+        if let Some(txid) = witnesses.keys().next() {
+            self.check_chain(XWitnessId::Bitcoin(*txid)).await?;
+        }
+
+        let fetched = join_all(witnesses.into_iter().map(|(txid, tx)| self.fetch(txid, tx))).await;
+
+        let mut resolver = PrefetchedResolver::default();
+        for res in fetched {
+            if let Some((tx, ord)) = res? {
+                resolver.witnesses.insert(tx.txid(), (tx, ord));
+            }
+        }
+
+        Ok(resolver)
+    }
+
+    async fn check_chain(&self, witness_id: XWitnessId) -> Result<(), WitnessResolverError> {
+        if !self.chain.is_pending() {
+            return Ok(());
+        }
+        let op = || self.client.get_block_hash(0);
+        let genesis_hash = op
+            .retry(default_backoff())
+            .when(is_retryable)
+            .await
+            .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()));
+        self.chain.ensure(witness_id, || genesis_hash)
+    }
+
+    async fn fetch(
+        &self,
+        txid: Txid,
+        known_tx: Option<Tx>,
+    ) -> Result<Option<(Tx, WitnessOrd)>, WitnessResolverError> {
+        let witness_id = XWitnessId::Bitcoin(txid);
+        let esplora_txid = to_esplora_txid(txid);
+
+        let op = || async {
+            let Some(tx) = self.client.get_tx(&esplora_txid).await? else {
+                return Ok(None);
+            };
+            let status = self.client.get_tx_status(&esplora_txid).await?;
+            Ok(Some((tx, status)))
+        };
+        let fetched = op
+            .retry(default_backoff())
+            .when(is_retryable)
+            .await
+            .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))?;
+        if let Some((tx, status)) = fetched {
+            let ord = esplora_status_to_ord(witness_id, &status)?;
+            return Ok(Some((from_bitcoin_tx(&tx), ord)));
+        }
+
+        let Some(tx) = known_tx else {
+            return Ok(None);
+        };
+        let spenders = join_all(spent_outputs(&tx).map(|prev_output| async move {
+            let prev_txid = to_esplora_txid(prev_output.txid);
+            let vout = prev_output.vout.into_u32() as u64;
+            let op = || self.client.get_output_status(&prev_txid, vout);
+            op.retry(default_backoff())
+                .when(is_retryable)
+                .await
+                .map(esplora_spender)
+                .map_err(|e| WitnessResolverError::Other(witness_id, e.to_string()))
+        }))
+        .await;
+        let ord = unknown_witness_ord(txid, spenders)?;
+
+        Ok(Some((tx, ord)))
+    }
+}
+
+/// Resolver answering from the witnesses fetched by `AsyncOnlineResolver`.
+#[derive(Default, Debug)]
+pub struct PrefetchedResolver {
+    witnesses: HashMap<Txid, (Tx, WitnessOrd)>,
+}
+
+impl ResolveWitness for PrefetchedResolver {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        let XWitnessId::Bitcoin(txid) = witness_id else {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                format!("{} is not supported as layer 1 network", witness_id.layer1()),
+            ));
+        };
+
+        self.witnesses
+            .get(&txid)
+            .map(|(tx, _)| XChain::Bitcoin(tx.clone()))
+            .ok_or(WitnessResolverError::Unknown(witness_id))
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let XWitnessId::Bitcoin(txid) = witness_id else {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                format!("{} is not supported as layer 1 network", witness_id.layer1()),
+            ));
+        };
+
+        self.witnesses
+            .get(&txid)
+            .map(|(_, ord)| *ord)
+            .ok_or(WitnessResolverError::Unknown(witness_id))
+    }
+}
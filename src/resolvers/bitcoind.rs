@@ -0,0 +1,278 @@
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::time::Duration;
+
+use amplify::hex::FromHex;
+use backon::BlockingRetryable;
+use base64::Engine;
+use bp::{ConsensusDecode, Tx};
+use rgbstd::{
+    validation::{ResolveWitness, WitnessResolverError},
+    vm::{WitnessOrd, WitnessPos, XWitnessTx},
+    XChain, XWitnessId,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{default_backoff, ChainCheck};
+use crate::types::Network;
+
+// Returned by bitcoind when it doesn't know the tx (RPC_INVALID_ADDRESS_OR_KEY).
+const RPC_NOT_FOUND: i64 = -5;
+// Returned by bitcoind while it's still loading (RPC_IN_WARMUP).
+const RPC_IN_WARMUP: i64 = -28;
+
+#[derive(Debug)]
+enum RpcError {
+    NotFound,
+    // Connection failure or node temporarily unavailable, worth retrying.
+    Transport(String),
+    // Any other answer, e.g. an authentication failure, would be the same the next time.
+    Other(String),
+}
+
+impl RpcError {
+    fn is_retryable(&self) -> bool {
+        matches!(self, Self::Transport(_))
+    }
+
+    fn into_message(self) -> String {
+        match self {
+            Self::NotFound => "not found".to_string(),
+            Self::Transport(e) | Self::Other(e) => e,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<Value>,
+    error: Option<RpcErrorObject>,
+}
+
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RawTransaction {
+    hex: String,
+    #[serde(default)]
+    confirmations: Option<i64>,
+    #[serde(default)]
+    blockhash: Option<String>,
+    #[serde(default)]
+    blocktime: Option<i64>,
+    #[serde(default)]
+    in_active_chain: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct BlockHeader {
+    height: u32,
+    time: i64,
+    // -1 for a block which is not in the active chain.
+    confirmations: i64,
+}
+
+/// Resolver backed by the JSON-RPC interface of Bitcoin Core.
+///
+/// Witnesses are looked up with `getrawtransaction`, so bitcoind must either
+/// run with `-txindex` or still have them in its mempool. A witness mined in
+/// a block which is no longer in the active chain is reported as archived.
+///
+/// Only connection failures and a node still warming up are retried.
+#[derive(Debug)]
+pub struct BitcoindResolver {
+    url: String,
+    auth: Option<String>,
+    timeout: Duration,
+    chain: ChainCheck,
+}
+
+impl BitcoindResolver {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            auth: None,
+            timeout: Duration::from_secs(30),
+            chain: ChainCheck::default(),
+        }
+    }
+
+    pub fn with_auth(url: &str, user: &str, password: &str) -> Self {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{user}:{password}"));
+        Self {
+            auth: Some(format!("Basic {credentials}")),
+            ..Self::new(url)
+        }
+    }
+
+    /// Timeout of each request, 30 seconds by default, rounded up to seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Refuses to resolve anything if the node is on another network, which
+    /// is checked against its genesis block on the first request.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.chain = ChainCheck::new(network);
+        self
+    }
+
+    /// Authenticate with the `.cookie` file written by bitcoind.
+    pub fn with_cookie_file(url: &str, cookie_file: impl AsRef<Path>) -> std::io::Result<Self> {
+        let cookie = std::fs::read_to_string(cookie_file)?;
+        let (user, password) = cookie.trim().split_once(':').ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid cookie file")
+        })?;
+        Ok(Self::with_auth(url, user, password))
+    }
+
+    fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        let body = json!({
+            "jsonrpc": "1.0",
+            "id": "rgb-coloring",
+            "method": method,
+            "params": params,
+        });
+
+        let timeout = self.timeout.as_secs() + u64::from(self.timeout.subsec_nanos() > 0);
+        let mut request = minreq::post(&self.url)
+            .with_header("Content-Type", "application/json")
+            .with_body(body.to_string())
+            .with_timeout(timeout);
+        if let Some(ref auth) = self.auth {
+            request = request.with_header("Authorization", auth);
+        }
+        let response = request.send().map_err(|e| RpcError::Transport(e.to_string()))?;
+
+        // bitcoind answers RPC errors with a non-2xx status, but still with a JSON body.
+        // Without one, only an overloaded node is worth asking again, not e.g. a wrong password.
+        let status = response.status_code;
+        let response: RpcResponse = serde_json::from_slice(response.as_bytes()).map_err(|e| {
+            let e = format!("invalid response (status {status}): {e}");
+            if status == 429 || status >= 500 {
+                RpcError::Transport(e)
+            } else {
+                RpcError::Other(e)
+            }
+        })?;
+        match (response.result, response.error) {
+            (_, Some(error)) if error.code == RPC_NOT_FOUND => Err(RpcError::NotFound),
+            (_, Some(error)) if error.code == RPC_IN_WARMUP => Err(RpcError::Transport(
+                format!("{} ({})", error.message, error.code),
+            )),
+            (_, Some(error)) => Err(RpcError::Other(format!(
+                "{} ({})",
+                error.message, error.code
+            ))),
+            (Some(result), None) => {
+                serde_json::from_value(result).map_err(|e| RpcError::Other(e.to_string()))
+            }
+            (None, None) => Err(RpcError::Other("empty response".to_string())),
+        }
+    }
+
+    fn get_raw_transaction(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<RawTransaction, WitnessResolverError> {
+        let XWitnessId::Bitcoin(txid) = witness_id else {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                format!(
+                    "{} is not supported as layer 1 network",
+                    witness_id.layer1()
+                ),
+            ));
+        };
+
+        self.chain.ensure(witness_id, || {
+            let op = || self.call::<String>("getblockhash", json!([0]));
+            let hash = op
+                .retry(default_backoff())
+                .when(RpcError::is_retryable)
+                .call()
+                .map_err(|e| WitnessResolverError::Other(witness_id, e.into_message()))?;
+            hash.parse().map_err(|_| {
+                WitnessResolverError::Other(witness_id, format!("invalid block hash {hash}"))
+            })
+        })?;
+
+        let op = || self.call("getrawtransaction", json!([txid.to_string(), true]));
+        op.retry(default_backoff())
+            .when(RpcError::is_retryable)
+            .call()
+            .map_err(|e| match e {
+                RpcError::NotFound => WitnessResolverError::Unknown(witness_id),
+                e => WitnessResolverError::Other(witness_id, e.into_message()),
+            })
+    }
+}
+
+impl ResolveWitness for BitcoindResolver {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        let raw_tx = self.get_raw_transaction(witness_id)?;
+
+        let invalid_data = |e: String| WitnessResolverError::Other(witness_id, e);
+        let bytes = Vec::<u8>::from_hex(&raw_tx.hex).map_err(|e| invalid_data(e.to_string()))?;
+        let tx = Tx::consensus_deserialize(bytes).map_err(|e| invalid_data(e.to_string()))?;
+
+        Ok(XChain::Bitcoin(tx))
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let raw_tx = self.get_raw_transaction(witness_id)?;
+
+        // Only txs which are mined or in the mempool are known to bitcoind,
+        // those in the mempool have no block.
+        let Some(blockhash) = raw_tx.blockhash else {
+            return Ok(WitnessOrd::Tentative);
+        };
+        // Its block was reorged out, and the tx didn't make it back to the mempool.
+        if raw_tx.confirmations.is_none_or(|confirmations| confirmations < 1)
+            || raw_tx.in_active_chain == Some(false)
+        {
+            return Ok(WitnessOrd::Archived);
+        }
+
+        let op = || self.call::<BlockHeader>("getblockheader", json!([blockhash, true]));
+        let header = op
+            .retry(default_backoff())
+            .when(RpcError::is_retryable)
+            .call()
+            .map_err(|e| match e {
+                RpcError::NotFound => "block of a confirmed tx is not found".to_string(),
+                e => e.into_message(),
+            })
+            .map_err(|e| WitnessResolverError::Other(witness_id, e))?;
+        // Reorged out between the two requests.
+        if header.confirmations < 1 {
+            return Ok(WitnessOrd::Archived);
+        }
+
+        let height = NonZeroU32::new(header.height).ok_or_else(|| {
+            WitnessResolverError::Other(witness_id, "Invalid block height".to_string())
+        })?;
+        let pos = WitnessPos::bitcoin(height, raw_tx.blocktime.unwrap_or(header.time)).ok_or_else(
+            || WitnessResolverError::Other(witness_id, "Invalid server data".to_string()),
+        )?;
+
+        Ok(WitnessOrd::Mined(pos))
+    }
+}
@@ -0,0 +1,404 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use amplify::hex::FromHex;
+use amplify::ByteArray;
+use backon::BlockingRetryable;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::TxMerkleNode;
+use bp::{ConsensusDecode, Outpoint, Tx, Txid};
+use rgbstd::{
+    validation::{ResolveWitness, WitnessResolverError},
+    vm::{WitnessOrd, WitnessPos, XWitnessTx},
+    XChain, XWitnessId,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::spv::merkle_root;
+use super::{default_backoff, spent_outputs, unknown_witness_ord, ChainCheck};
+use crate::types::Network;
+
+// Code of the errors relayed by ElectrumX and Fulcrum from their bitcoind, whose
+// own code is embedded in the message.
+const DAEMON_ERROR: i64 = 2;
+// Code of bitcoind for an unknown tx (RPC_INVALID_ADDRESS_OR_KEY).
+const RPC_NOT_FOUND: i64 = -5;
+
+#[derive(Debug)]
+enum RpcError {
+    // Connection or protocol failure, worth retrying.
+    Transport(String),
+    // Error reported by the server.
+    Server { code: i64, message: String },
+}
+
+impl RpcError {
+    fn is_not_found(&self) -> bool {
+        match self {
+            Self::Server { code: RPC_NOT_FOUND, .. } => true,
+            Self::Server { code: DAEMON_ERROR, message } => {
+                daemon_error_code(message) == Some(RPC_NOT_FOUND)
+            }
+            _ => false,
+        }
+    }
+
+    fn into_message(self) -> String {
+        match self {
+            Self::Transport(message) | Self::Server { message, .. } => message,
+        }
+    }
+}
+
+// Relayed errors look like `daemon error: DaemonError({'code': -5, 'message': ...})`.
+fn daemon_error_code(message: &str) -> Option<i64> {
+    let rest = &message[message.find("code")? + "code".len()..];
+    let rest = rest.trim_start_matches(['\'', '"', ':', ' ']);
+    let end = rest
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+        .map_or(rest.len(), |(i, _)| i);
+    rest[..end].parse().ok()
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<Value>,
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct HistoryItem {
+    tx_hash: String,
+    height: i64,
+}
+
+#[derive(Deserialize)]
+struct MerkleProof {
+    block_height: u32,
+    merkle: Vec<String>,
+    pos: usize,
+}
+
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+/// Resolver backed by an Electrum server, e.g. Electrs or Fulcrum.
+///
+/// Only plain TCP connections are supported.
+///
+/// The server is trusted: mined positions are checked against a merkle proof,
+/// but the block header comes from the same server, so this only catches an
+/// inconsistent answer, not a block which isn't in the most-work chain. A
+/// witness the server knows, but which is neither mined nor in its mempool,
+/// is archived if one of its inputs was spent by another tx according to the
+/// history of the spent output, and tentative otherwise. Use `OnlineResolver`
+/// in SPV mode to verify confirmations against a header chain.
+#[derive(Debug)]
+pub struct ElectrumResolver {
+    addr: String,
+    timeout: Duration,
+    conn: Mutex<Option<Connection>>,
+    next_id: Mutex<u64>,
+    chain: ChainCheck,
+}
+
+impl ElectrumResolver {
+    /// `addr` is the `host:port` of the server.
+    pub fn new(addr: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            timeout: Duration::from_secs(30),
+            conn: Mutex::new(None),
+            next_id: Mutex::new(0),
+            chain: ChainCheck::default(),
+        }
+    }
+
+    /// Refuses to resolve anything if the server is on another network,
+    /// which is checked against its genesis block on the first request.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.chain = ChainCheck::new(network);
+        self
+    }
+
+    fn connect(&self) -> std::io::Result<Connection> {
+        let stream = TcpStream::connect(&self.addr)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, RpcError> {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let mut conn = self.conn.lock().unwrap();
+        let res = (|| -> std::io::Result<Value> {
+            if conn.is_none() {
+                *conn = Some(self.connect()?);
+            }
+            let Some(conn) = conn.as_mut() else {
+                unreachable!()
+            };
+            writeln!(conn.writer, "{request}")?;
+            conn.writer.flush()?;
+
+            // Skip notifications and responses to other requests.
+            loop {
+                let mut line = String::new();
+                if conn.reader.read_line(&mut line)? == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+                let response: Value = serde_json::from_str(&line)?;
+                if response.get("id").and_then(Value::as_u64) == Some(id) {
+                    break Ok(response);
+                }
+            }
+        })();
+        let response = match res {
+            Ok(response) => response,
+            Err(e) => {
+                // The connection is in an unknown state, start over next time.
+                *conn = None;
+                return Err(RpcError::Transport(e.to_string()));
+            }
+        };
+        drop(conn);
+
+        let response: RpcResponse =
+            serde_json::from_value(response).map_err(|e| RpcError::Transport(e.to_string()))?;
+        match (response.result, response.error) {
+            (_, Some(error)) => {
+                let message = error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| error.to_string());
+                let code = error.get("code").and_then(Value::as_i64).unwrap_or_default();
+                Err(RpcError::Server { code, message })
+            }
+            (Some(result), None) => {
+                serde_json::from_value(result).map_err(|e| RpcError::Transport(e.to_string()))
+            }
+            (None, None) => Err(RpcError::Transport("empty response".to_string())),
+        }
+    }
+
+    fn call_with_retry<T: for<'de> Deserialize<'de>>(
+        &self,
+        witness_id: XWitnessId,
+        method: &str,
+        params: Value,
+    ) -> Result<T, WitnessResolverError> {
+        let op = || self.call(method, params.clone());
+        op.retry(default_backoff())
+            .when(|e| matches!(e, RpcError::Transport(_)))
+            .call()
+            .map_err(|e| WitnessResolverError::Other(witness_id, e.into_message()))
+    }
+
+    fn get_tx(&self, witness_id: XWitnessId) -> Result<Tx, WitnessResolverError> {
+        let XWitnessId::Bitcoin(txid) = witness_id else {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                format!(
+                    "{} is not supported as layer 1 network",
+                    witness_id.layer1()
+                ),
+            ));
+        };
+        self.chain.ensure(witness_id, || {
+            let header: String =
+                self.call_with_retry(witness_id, "blockchain.block.header", json!([0]))?;
+            let header: bitcoin::block::Header = Vec::<u8>::from_hex(&header)
+                .ok()
+                .and_then(|bytes| bitcoin::consensus::deserialize(&bytes).ok())
+                .ok_or_else(|| {
+                    WitnessResolverError::Other(witness_id, "invalid block header".to_string())
+                })?;
+            Ok(header.block_hash())
+        })?;
+        self.fetch_tx(witness_id, txid)
+    }
+
+    // Any tx, errors being reported for `witness_id`.
+    fn fetch_tx(&self, witness_id: XWitnessId, txid: Txid) -> Result<Tx, WitnessResolverError> {
+        let op = || self.call::<String>("blockchain.transaction.get", json!([txid.to_string()]));
+        let hex = op
+            .retry(default_backoff())
+            .when(|e| matches!(e, RpcError::Transport(_)))
+            .call()
+            .map_err(|e| {
+                if e.is_not_found() {
+                    WitnessResolverError::Unknown(witness_id)
+                } else {
+                    WitnessResolverError::Other(witness_id, e.into_message())
+                }
+            })?;
+
+        let invalid_data = |e: String| WitnessResolverError::Other(witness_id, e);
+        let bytes = Vec::<u8>::from_hex(&hex).map_err(|e| invalid_data(e.to_string()))?;
+        let tx = Tx::consensus_deserialize(bytes).map_err(|e| invalid_data(e.to_string()))?;
+        if tx.txid() != txid {
+            return Err(WitnessResolverError::IdMismatch {
+                actual: XWitnessId::Bitcoin(tx.txid()),
+                expected: XWitnessId::Bitcoin(txid),
+            });
+        }
+
+        Ok(tx)
+    }
+
+    // Electrum servers only index txs by the scripts they touch.
+    fn script_history(
+        &self,
+        witness_id: XWitnessId,
+        script: &[u8],
+    ) -> Result<Vec<HistoryItem>, WitnessResolverError> {
+        let mut script_hash = sha256::Hash::hash(script).to_byte_array();
+        script_hash.reverse();
+        let script_hash = amplify::hex::ToHex::to_hex(script_hash.as_slice());
+
+        self.call_with_retry(
+            witness_id,
+            "blockchain.scripthash.get_history",
+            json!([script_hash]),
+        )
+    }
+
+    // Tx spending `prev_output` other than the witness, if any.
+    fn spender(
+        &self,
+        witness_id: XWitnessId,
+        txid: Txid,
+        prev_output: Outpoint,
+    ) -> Result<Option<Txid>, WitnessResolverError> {
+        // The witness is known, so must be the txs it spends.
+        let fetch_tx = |txid| {
+            self.fetch_tx(witness_id, txid).map_err(|e| match e {
+                WitnessResolverError::Unknown(_) => {
+                    WitnessResolverError::Other(witness_id, format!("unknown tx {txid}"))
+                }
+                e => e,
+            })
+        };
+
+        let prev_tx = fetch_tx(prev_output.txid)?;
+        let Some(spent) = prev_tx.outputs.get(prev_output.vout.into_u32() as usize) else {
+            return Err(WitnessResolverError::Other(
+                witness_id,
+                format!("witness spends a missing output of {}", prev_output.txid),
+            ));
+        };
+        for item in self.script_history(witness_id, spent.script_pubkey.as_slice())? {
+            let other_txid = item.tx_hash.parse::<Txid>().map_err(|e| {
+                WitnessResolverError::Other(witness_id, format!("invalid history: {e}"))
+            })?;
+            if other_txid == txid || other_txid == prev_output.txid {
+                continue;
+            }
+            let other_tx = fetch_tx(other_txid)?;
+            if other_tx.inputs.iter().any(|txin| txin.prev_output == prev_output) {
+                return Ok(Some(other_txid));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl ResolveWitness for ElectrumResolver {
+    fn resolve_pub_witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<XWitnessTx, WitnessResolverError> {
+        self.get_tx(witness_id).map(XChain::Bitcoin)
+    }
+
+    fn resolve_pub_witness_ord(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<WitnessOrd, WitnessResolverError> {
+        let tx = self.get_tx(witness_id)?;
+        let txid = tx.txid();
+        let invalid_data = |e: &str| WitnessResolverError::Other(witness_id, e.to_string());
+
+        // Look up the history of one of the outputs, OP_RETURN ones are never indexed.
+        let script = tx
+            .outputs
+            .iter()
+            .map(|txout| txout.script_pubkey.as_slice())
+            .find(|script| script.first() != Some(&0x6a))
+            .ok_or_else(|| invalid_data("witness has no indexed output"))?;
+        let history = self.script_history(witness_id, script)?;
+        // Neither mined nor in the mempool, e.g. replaced or not relayed.
+        let Some(item) = history
+            .into_iter()
+            .find(|item| item.tx_hash == txid.to_string())
+        else {
+            let spenders = spent_outputs(&tx).map(|o| self.spender(witness_id, txid, o));
+            return unknown_witness_ord(txid, spenders);
+        };
+        // 0 means in the mempool, -1 in the mempool with unconfirmed inputs.
+        let Ok(height) = u32::try_from(item.height) else {
+            return Ok(WitnessOrd::Tentative);
+        };
+        let Some(height) = NonZeroU32::new(height) else {
+            return Ok(WitnessOrd::Tentative);
+        };
+
+        let proof: MerkleProof = self.call_with_retry(
+            witness_id,
+            "blockchain.transaction.get_merkle",
+            json!([txid.to_string(), height.get()]),
+        )?;
+        if proof.block_height != height.get() {
+            return Err(invalid_data("merkle proof is for another block"));
+        }
+        let header: String =
+            self.call_with_retry(witness_id, "blockchain.block.header", json!([height.get()]))?;
+        let header: bitcoin::block::Header = Vec::<u8>::from_hex(&header)
+            .ok()
+            .and_then(|bytes| bitcoin::consensus::deserialize(&bytes).ok())
+            .ok_or_else(|| invalid_data("invalid block header"))?;
+
+        let merkle_branch = proof
+            .merkle
+            .iter()
+            .map(|node| node.parse::<TxMerkleNode>().map(TxMerkleNode::to_raw_hash))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid_data("invalid merkle proof"))?;
+        let txid = bitcoin::Txid::from_byte_array(txid.to_byte_array());
+        if merkle_root(txid, merkle_branch, proof.pos) != header.merkle_root {
+            return Err(invalid_data("witness is not included in the block"));
+        }
+
+        let pos = WitnessPos::bitcoin(height, header.time as i64)
+            .ok_or_else(|| invalid_data("Invalid server data"))?;
+        Ok(WitnessOrd::Mined(pos))
+    }
+}
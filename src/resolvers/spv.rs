@@ -0,0 +1,174 @@
+use bitcoin::block::Header;
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin::params::Params;
+use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+
+use crate::error::NetworkError;
+use crate::types::Network;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpvError {
+    /// Header doesn't build on top of the chain tip.
+    Disconnected { height: u32 },
+    /// Header hash doesn't satisfy its target, or the target is too easy.
+    InvalidProofOfWork { height: u32 },
+    /// Header target doesn't follow the difficulty adjustment rules.
+    UnexpectedDifficulty { height: u32 },
+    /// No header known at this height, either above the tip or below the checkpoint.
+    UnknownHeight { height: u32 },
+    /// Tx is not included in the block at this height.
+    MerkleRootMismatch { height: u32 },
+}
+
+impl std::fmt::Display for SpvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Disconnected { height } => {
+                write!(f, "header at height {height} is not connected to the chain")
+            }
+            Self::InvalidProofOfWork { height } => {
+                write!(f, "header at height {height} has an invalid proof of work")
+            }
+            Self::UnexpectedDifficulty { height } => {
+                write!(f, "header at height {height} has an unexpected difficulty")
+            }
+            Self::UnknownHeight { height } => write!(f, "no known header at height {height}"),
+            Self::MerkleRootMismatch { height } => {
+                write!(f, "tx is not included in the block at height {height}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpvError {}
+
+/// Chain of block headers, validated from a trusted checkpoint.
+///
+/// Each header must connect to the previous one and carry a valid proof of
+/// work. The difficulty adjustment rules are also checked on networks which
+/// don't allow min difficulty blocks, as long as the start of the adjustment
+/// period is known.
+#[derive(Clone, Debug)]
+pub struct HeaderChain {
+    params: Params,
+    checkpoint_height: u32,
+    // The first one is the checkpoint.
+    headers: Vec<Header>,
+}
+
+impl HeaderChain {
+    /// Fails on Liquid, whose headers are not validated.
+    pub fn new(
+        network: Network,
+        checkpoint_height: u32,
+        checkpoint: Header,
+    ) -> Result<Self, NetworkError> {
+        Ok(Self {
+            params: Params::new(bitcoin::Network::try_from(network)?),
+            checkpoint_height,
+            headers: vec![checkpoint],
+        })
+    }
+
+    pub fn checkpoint_height(&self) -> u32 {
+        self.checkpoint_height
+    }
+
+    pub fn tip_height(&self) -> u32 {
+        self.checkpoint_height + self.headers.len() as u32 - 1
+    }
+
+    pub fn tip_hash(&self) -> BlockHash {
+        self.tip().block_hash()
+    }
+
+    pub fn header_at(&self, height: u32) -> Option<&Header> {
+        let index = height.checked_sub(self.checkpoint_height)?;
+        self.headers.get(index as usize)
+    }
+
+    fn tip(&self) -> &Header {
+        self.headers.last().expect("checkpoint is always present")
+    }
+
+    /// Extends the chain with the header of the next block.
+    pub fn push(&mut self, header: Header) -> Result<(), SpvError> {
+        let height = self.tip_height() + 1;
+        let prev = self.tip();
+
+        if header.prev_blockhash != prev.block_hash() {
+            return Err(SpvError::Disconnected { height });
+        }
+
+        let target = header.target();
+        if target > self.params.max_attainable_target || header.validate_pow(target).is_err() {
+            return Err(SpvError::InvalidProofOfWork { height });
+        }
+
+        if !self.params.allow_min_difficulty_blocks {
+            let interval = self.params.difficulty_adjustment_interval() as u32;
+            let expected_bits = if !height.is_multiple_of(interval) {
+                Some(prev.bits)
+            } else {
+                self.header_at(height - interval).map(|epoch_start| {
+                    CompactTarget::from_header_difficulty_adjustment(
+                        *epoch_start,
+                        *prev,
+                        &self.params,
+                    )
+                })
+            };
+            if expected_bits.is_some_and(|bits| bits != header.bits) {
+                return Err(SpvError::UnexpectedDifficulty { height });
+            }
+        }
+
+        self.headers.push(header);
+        Ok(())
+    }
+
+    /// Drops the headers above `height`, e.g. after a reorg. The checkpoint is always kept.
+    pub fn rewind(&mut self, height: u32) {
+        let len = height.saturating_sub(self.checkpoint_height) as usize + 1;
+        self.headers.truncate(len);
+    }
+
+    /// Checks the merkle proof of `txid` at position `pos` in the block at `height`.
+    pub fn verify_inclusion(
+        &self,
+        txid: bitcoin::Txid,
+        height: u32,
+        merkle_branch: impl IntoIterator<Item = sha256d::Hash>,
+        pos: usize,
+    ) -> Result<&Header, SpvError> {
+        let header = self
+            .header_at(height)
+            .ok_or(SpvError::UnknownHeight { height })?;
+        if merkle_root(txid, merkle_branch, pos) != header.merkle_root {
+            return Err(SpvError::MerkleRootMismatch { height });
+        }
+        Ok(header)
+    }
+}
+
+/// Computes the merkle root from a tx and its merkle branch, as returned by
+/// Esplora and Electrum servers.
+pub(crate) fn merkle_root(
+    txid: bitcoin::Txid,
+    merkle_branch: impl IntoIterator<Item = sha256d::Hash>,
+    pos: usize,
+) -> TxMerkleNode {
+    let mut node = txid.to_raw_hash();
+    for (depth, sibling) in merkle_branch.into_iter().enumerate() {
+        let mut engine = sha256d::Hash::engine();
+        if pos.checked_shr(depth as u32).unwrap_or(0) & 1 == 0 {
+            engine.input(node.as_byte_array());
+            engine.input(sibling.as_byte_array());
+        } else {
+            engine.input(sibling.as_byte_array());
+            engine.input(node.as_byte_array());
+        }
+        node = sha256d::Hash::from_engine(engine);
+    }
+    TxMerkleNode::from_raw_hash(node)
+}
@@ -0,0 +1,128 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use amplify::confinement::U32 as U32MAX;
+use nonasync::persistence::{PersistenceError, PersistenceProvider};
+use rgbstd::persistence::{MemIndex, MemStash, MemState};
+use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+mod sqlite;
+
+pub use sqlite::{SqliteError, SqliteIndex, SqliteStash, SqliteState, SqliteStock};
+pub(crate) use sqlite::open as open_sqlite;
+
+const LOCK_FILE: &str = "LOCK";
+
+/// Same layout as `FsBinStore`, but files are replaced atomically and the
+/// directory is locked for as long as the store is in use.
+///
+/// Each file is durable once its `store` returns, but the three of them are
+/// written one after another: a stock operation stores the index, then the
+/// state, then the stash. A crash in between leaves the files from different
+/// operations, and the stock may then fail to load or miss the last change.
+/// Use the SQLite store where this window matters.
+#[derive(Clone, Debug)]
+pub(crate) struct LockedFsStore {
+    stash: PathBuf,
+    state: PathBuf,
+    index: PathBuf,
+    // Shared by all the clones, the lock is released once the last one is dropped.
+    _lock: Arc<File>,
+}
+
+impl LockedFsStore {
+    pub(crate) fn new(path: &Path) -> io::Result<Self> {
+        fs::create_dir_all(path)?;
+
+        let lock = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.join(LOCK_FILE))?;
+        lock.try_lock().map_err(|e| match e {
+            fs::TryLockError::WouldBlock => io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("stock at {} is used by another process", path.display()),
+            ),
+            fs::TryLockError::Error(e) => e,
+        })?;
+
+        Ok(Self {
+            stash: path.join("stash.dat"),
+            state: path.join("state.dat"),
+            index: path.join("index.dat"),
+            _lock: Arc::new(lock),
+        })
+    }
+
+    pub(crate) fn exists(&self) -> bool {
+        self.stash.exists()
+    }
+}
+
+// Writes to a temp file first, so that a crash never leaves a partially
+// written file behind. The directory is synced too, otherwise the rename
+// itself may be lost on a crash.
+fn store_atomically<T: StrictSerialize>(object: &T, path: &Path) -> Result<(), PersistenceError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    object
+        .strict_serialize_to_file::<U32MAX>(&tmp)
+        .map_err(PersistenceError::with)?;
+    File::open(&tmp)
+        .and_then(|file| file.sync_all())
+        .and_then(|_| fs::rename(&tmp, path))
+        .and_then(|_| sync_dir(path))
+        .map_err(PersistenceError::with)
+}
+
+#[cfg(unix)]
+pub(crate) fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+// Directories can't be opened for syncing on windows.
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl PersistenceProvider<MemStash> for LockedFsStore {
+    fn load(&self) -> Result<MemStash, PersistenceError> {
+        MemStash::strict_deserialize_from_file::<U32MAX>(&self.stash)
+            .map_err(PersistenceError::with)
+    }
+
+    fn store(&self, object: &MemStash) -> Result<(), PersistenceError> {
+        store_atomically(object, &self.stash)
+    }
+}
+
+impl PersistenceProvider<MemState> for LockedFsStore {
+    fn load(&self) -> Result<MemState, PersistenceError> {
+        MemState::strict_deserialize_from_file::<U32MAX>(&self.state)
+            .map_err(PersistenceError::with)
+    }
+
+    fn store(&self, object: &MemState) -> Result<(), PersistenceError> {
+        store_atomically(object, &self.state)
+    }
+}
+
+impl PersistenceProvider<MemIndex> for LockedFsStore {
+    fn load(&self) -> Result<MemIndex, PersistenceError> {
+        MemIndex::strict_deserialize_from_file::<U32MAX>(&self.index)
+            .map_err(PersistenceError::with)
+    }
+
+    fn store(&self, object: &MemIndex) -> Result<(), PersistenceError> {
+        store_atomically(object, &self.index)
+    }
+}
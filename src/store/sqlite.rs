@@ -0,0 +1,1942 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::io;
+use std::mem;
+use std::num::NonZeroU32;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use aluvm::library::{Lib, LibId};
+use amplify::confinement::{self, Confined, MediumBlob, U32 as U32MAX};
+use bp::dbc::tapret::TapretCommitment;
+use commit_verify::{CommitId, Conceal};
+use nonasync::persistence::{CloneNoPersistence, Persistence, Persisting};
+use rgbstd::containers::{
+    AnchorSet, ContentId, ContentRef, ContentSigs, Fascia, SealWitness, SigBlob, Supplement,
+    TrustLevel, ValidContract, ValidKit, ValidTransfer,
+};
+use rgbstd::interface::{Iface, IfaceClass, IfaceId, IfaceImpl, IfaceRef};
+use rgbstd::persistence::{
+    ContractIfaceError, ContractStateRead, ContractStateWrite, IndexInconsistency, IndexProvider,
+    IndexReadError, IndexReadProvider, IndexWriteError, IndexWriteProvider, MemContract,
+    MemContractState, SchemaIfaces, StashInconsistency, StashProvider, StashProviderError,
+    StashReadProvider, StashWriteProvider, StateInconsistency, StateProvider, StateReadProvider,
+    StateWriteProvider, Stock, StockError, StoreTransaction, UpdateRes,
+};
+use rgbstd::persistence::FasciaError;
+use rgbstd::validation::{ResolveWitness, Status};
+use rgbstd::vm::{
+    ContractStateAccess, ContractStateEvolve, GlobalContractState, GlobalStateIter, OrdOpRef,
+    UnknownGlobalStateType, WitnessOrd,
+};
+use rgbstd::{
+    Assign, AssignmentType, AttachId, AttachState, BundleId, ContractId, DataState, ExposedSeal,
+    ExposedState, Extension, FungibleState, Genesis, GenesisSeal, GraphSeal, Identity, OpId,
+    Operation, Opout, OutputAssignment, RevealedAttach, RevealedData, RevealedValue, Schema,
+    SchemaId, SecretSeal, Transition, TransitionBundle, VoidState, XChain, XOutpoint,
+    XOutputSeal, XWitnessId,
+};
+use rusqlite::{params, Connection};
+use strict_encoding::{StreamReader, StrictDecode, StrictEncode, StrictReader, StrictWriter};
+use strict_types::TypeSystem;
+
+/// Stock keeping its stash, state and index in a SQLite database.
+///
+/// Derefs to `Stock`. When an operation fails halfway, `Stock` leaves its
+/// changes in place. The operations below, which write to several providers
+/// at once, roll them back from both the database and memory before the
+/// error is returned. Run on the inner `Stock`, e.g. by a function generic
+/// over the providers, the rollback is deferred to the next operation.
+#[derive(Debug)]
+pub struct SqliteStock(Stock<SqliteStash, SqliteState, SqliteIndex>);
+
+type SqliteStockError<E = Infallible> = StockError<SqliteStash, SqliteState, SqliteIndex, E>;
+
+impl SqliteStock {
+    pub fn import_kit(&mut self, kit: ValidKit) -> Result<Status, SqliteStockError> {
+        let res = self.0.import_kit(kit);
+        self.rollback_failed(res)
+    }
+
+    pub fn import_contract<R: ResolveWitness>(
+        &mut self,
+        contract: ValidContract,
+        resolver: R,
+    ) -> Result<Status, SqliteStockError> {
+        let res = self.0.import_contract(contract, resolver);
+        self.rollback_failed(res)
+    }
+
+    pub fn accept_transfer<R: ResolveWitness>(
+        &mut self,
+        transfer: ValidTransfer,
+        resolver: R,
+    ) -> Result<Status, SqliteStockError> {
+        let res = self.0.accept_transfer(transfer, resolver);
+        self.rollback_failed(res)
+    }
+
+    pub fn consume_fascia<R: ResolveWitness>(
+        &mut self,
+        fascia: Fascia,
+        resolver: R,
+    ) -> Result<(), SqliteStockError<FasciaError>> {
+        let res = self.0.consume_fascia(fascia, resolver);
+        self.rollback_failed(res)
+    }
+
+    fn rollback_failed<T, E: std::error::Error>(
+        &mut self,
+        res: Result<T, SqliteStockError<E>>,
+    ) -> Result<T, SqliteStockError<E>> {
+        if res.is_err() {
+            self.0.as_stash_provider().journal.rollback();
+            self.0
+                .as_stash_provider_mut()
+                .refresh()
+                .and_then(|_| self.0.as_state_provider_mut().refresh())
+                .and_then(|_| self.0.as_index_provider_mut().refresh())
+                .map_err(SqliteStockError::StashWrite)?;
+        }
+        res
+    }
+}
+
+impl Deref for SqliteStock {
+    type Target = Stock<SqliteStash, SqliteState, SqliteIndex>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SqliteStock {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(Debug)]
+pub enum SqliteError {
+    Database(rusqlite::Error),
+    Confinement(confinement::Error),
+    /// Data read from the database can't be decoded.
+    InvalidData(String),
+}
+
+impl std::fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Database(e) => write!(f, "database error: {e}"),
+            Self::Confinement(e) => write!(f, "{e}"),
+            Self::InvalidData(e) => write!(f, "invalid data in the database: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SqliteError {}
+
+impl From<rusqlite::Error> for SqliteError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+impl From<confinement::Error> for SqliteError {
+    fn from(e: confinement::Error) -> Self {
+        Self::Confinement(e)
+    }
+}
+
+// Providers share three generic tables, rows are told apart by their kind.
+// Keys and values are strict encoded.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS maps (
+        kind TEXT NOT NULL, key BLOB NOT NULL, value BLOB NOT NULL,
+        PRIMARY KEY (kind, key)
+    );
+    CREATE TABLE IF NOT EXISTS sets (
+        kind TEXT NOT NULL, key BLOB NOT NULL, value BLOB NOT NULL,
+        PRIMARY KEY (kind, key, value)
+    );
+    CREATE TABLE IF NOT EXISTS log (
+        seq INTEGER PRIMARY KEY, kind TEXT NOT NULL, key BLOB NOT NULL, value BLOB NOT NULL
+    );
+";
+
+const STASH: &str = "stash";
+const STASH_SCHEMA: &str = "stash.schema";
+const STASH_IFACE: &str = "stash.iface";
+const STASH_IIMPL: &str = "stash.iimpl";
+const STASH_TRUST: &str = "stash.trust";
+const STASH_SUPPL: &str = "stash.suppl";
+const STASH_GENESIS: &str = "stash.genesis";
+const STASH_EXTENSION: &str = "stash.extension";
+const STASH_BUNDLE: &str = "stash.bundle";
+const STASH_WITNESS: &str = "stash.witness";
+const STASH_ATTACHMENT: &str = "stash.attachment";
+const STASH_TYPES: &str = "stash.types";
+const STASH_LIB: &str = "stash.lib";
+const STASH_SIGS: &str = "stash.sigs";
+const STASH_SECRET_SEAL: &str = "stash.secret_seal";
+
+const STATE: &str = "state";
+const STATE_CONTRACT: &str = "state.contract";
+const STATE_OP: &str = "state.op";
+const STATE_WITNESS: &str = "state.witness";
+
+const INDEX: &str = "index";
+const INDEX_CONTRACT: &str = "index.contract";
+const INDEX_OP_BUNDLE: &str = "index.op_bundle";
+const INDEX_BUNDLE_CONTRACT: &str = "index.bundle_contract";
+const INDEX_BUNDLE_WITNESS: &str = "index.bundle_witness";
+const INDEX_OUTPUT_OPOUT: &str = "index.output_opout";
+const INDEX_TERMINAL: &str = "index.terminal";
+
+// Key and value.
+type Row = (Vec<u8>, Vec<u8>);
+
+type Reader<'a> = StrictReader<StreamReader<io::Cursor<&'a [u8]>>>;
+
+fn encode<T: StrictEncode>(value: &T) -> Vec<u8> {
+    value
+        .strict_encode(StrictWriter::in_memory::<U32MAX>())
+        .expect("in-memory encoding")
+        .unbox()
+        .unconfine()
+}
+
+fn read<T: StrictDecode>(reader: &mut Reader) -> Result<T, SqliteError> {
+    T::strict_decode(reader).map_err(|e| SqliteError::InvalidData(e.to_string()))
+}
+
+fn decode<T: StrictDecode>(data: &[u8]) -> Result<T, SqliteError> {
+    read(&mut StrictReader::in_memory::<U32MAX>(data))
+}
+
+#[derive(Debug)]
+struct Database {
+    conn: Connection,
+    // Providers which began the ongoing transaction and didn't commit yet.
+    active: BTreeSet<&'static str>,
+    // Whether a transaction was begun and neither committed nor rolled back.
+    pending: bool,
+    // Bumped on every rollback, so that providers know their data is stale.
+    generation: u64,
+}
+
+impl Database {
+    fn rollback(&mut self) {
+        self.active.clear();
+        if !self.pending {
+            return;
+        }
+        // Fails if SQLite already rolled back on its own, e.g. after a failed commit.
+        let _ = self.conn.execute_batch("ROLLBACK");
+        self.pending = false;
+        self.generation += 1;
+    }
+}
+
+/// Connection shared by the providers of a stock.
+///
+/// SQLite can't nest transactions, so the first provider to begin one starts
+/// it and the last one to commit commits it. This way all the changes made
+/// by a `Stock` operation, e.g. `consume_fascia`, are applied at once or not
+/// at all.
+#[derive(Clone, Debug, Default)]
+struct Journal {
+    db: Option<Arc<Mutex<Database>>>,
+    generation: u64,
+    // Whether the provider wrote anything since its changes were last known
+    // to be committed.
+    dirty: bool,
+}
+
+impl Journal {
+    fn detached(&self) -> Self {
+        Self::default()
+    }
+
+    fn execute(
+        &mut self,
+        sql: &str,
+        kind: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), SqliteError> {
+        self.dirty = true;
+        if let Some(db) = &self.db {
+            db.lock()
+                .unwrap()
+                .conn
+                .prepare_cached(sql)?
+                .execute(params![kind, key, value])?;
+        }
+        Ok(())
+    }
+
+    fn put(&mut self, kind: &str, key: &[u8], value: &[u8]) -> Result<(), SqliteError> {
+        self.execute(
+            "INSERT OR REPLACE INTO maps (kind, key, value) VALUES (?1, ?2, ?3)",
+            kind,
+            key,
+            value,
+        )
+    }
+
+    fn add(&mut self, kind: &str, key: &[u8], value: &[u8]) -> Result<(), SqliteError> {
+        self.execute(
+            "INSERT OR IGNORE INTO sets (kind, key, value) VALUES (?1, ?2, ?3)",
+            kind,
+            key,
+            value,
+        )
+    }
+
+    fn append(&mut self, kind: &str, key: &[u8], value: &[u8]) -> Result<(), SqliteError> {
+        self.execute(
+            "INSERT INTO log (kind, key, value) VALUES (?1, ?2, ?3)",
+            kind,
+            key,
+            value,
+        )
+    }
+
+    fn values(&self, table: &str, kind: &str, key: &[u8]) -> Result<Vec<Vec<u8>>, SqliteError> {
+        let Some(db) = &self.db else {
+            return Ok(vec![]);
+        };
+        let db = db.lock().unwrap();
+        let mut stmt = db.conn.prepare_cached(&format!(
+            "SELECT value FROM {table} WHERE kind = ?1 AND key = ?2 ORDER BY rowid"
+        ))?;
+        let values = stmt
+            .query_map(params![kind, key], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(values)
+    }
+
+    fn rows(&self, table: &str, kind: &str) -> Result<Vec<Row>, SqliteError> {
+        let Some(db) = &self.db else {
+            return Ok(vec![]);
+        };
+        let db = db.lock().unwrap();
+        let mut stmt = db.conn.prepare_cached(&format!(
+            "SELECT key, value FROM {table} WHERE kind = ?1 ORDER BY rowid"
+        ))?;
+        let rows = stmt
+            .query_map([kind], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    fn begin(&mut self, provider: &'static str) -> Result<(), SqliteError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let mut db = db.lock().unwrap();
+        // `Stock` doesn't roll back when an operation fails halfway. Unless
+        // the operation was run through `SqliteStock`, the transaction it left
+        // behind is only noticed here.
+        // A failed commit leaves the transaction pending with no provider
+        // in it.
+        if db.active.contains(provider) || (db.active.is_empty() && db.pending) {
+            db.rollback();
+        }
+        if db.active.is_empty() {
+            db.conn.execute_batch("BEGIN IMMEDIATE")?;
+            db.pending = true;
+        }
+        db.active.insert(provider);
+        Ok(())
+    }
+
+    fn commit(&mut self, provider: &'static str) -> Result<(), SqliteError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let mut db = db.lock().unwrap();
+        db.active.remove(provider);
+        if db.active.is_empty() {
+            db.conn.execute_batch("COMMIT")?;
+            db.pending = false;
+        }
+        Ok(())
+    }
+
+    /// Rolls back the ongoing transaction, if any. The providers then have to
+    /// drop their changes, see `is_stale`.
+    fn rollback(&self) {
+        if let Some(db) = &self.db {
+            db.lock().unwrap().rollback();
+        }
+    }
+
+    /// Returns whether a transaction was rolled back since the provider last
+    /// dropped its changes.
+    fn is_stale(&self) -> bool {
+        self.db
+            .as_ref()
+            .is_some_and(|db| db.lock().unwrap().generation != self.generation)
+    }
+
+    fn synced(&mut self) {
+        if let Some(db) = &self.db {
+            self.generation = db.lock().unwrap().generation;
+        }
+        self.dirty = false;
+    }
+}
+
+/// Opens the database at `path`, creating it if needed, and loads the
+/// providers from it.
+///
+/// The database is locked for as long as the providers are alive.
+pub(crate) fn open(path: &Path) -> Result<SqliteStock, SqliteError> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA locking_mode = EXCLUSIVE; PRAGMA synchronous = FULL;")?;
+    conn.execute_batch(SCHEMA)?;
+
+    let journal = Journal {
+        db: Some(Arc::new(Mutex::new(Database {
+            conn,
+            active: BTreeSet::new(),
+            pending: false,
+            generation: 0,
+        }))),
+        generation: 0,
+        dirty: false,
+    };
+    Ok(SqliteStock(Stock::with(
+        SqliteStash::load(journal.clone())?,
+        SqliteState::load(journal.clone())?,
+        SqliteIndex::load(journal)?,
+    )))
+}
+
+//////////
+// STASH
+//////////
+
+#[derive(Debug)]
+pub struct SqliteStash {
+    journal: Journal,
+    persistence: Option<Persistence<Self>>,
+
+    schemata: BTreeMap<SchemaId, SchemaIfaces>,
+    ifaces: BTreeMap<IfaceId, Iface>,
+    geneses: BTreeMap<ContractId, Genesis>,
+    suppl: BTreeMap<ContentRef, BTreeSet<Supplement>>,
+    bundles: BTreeMap<BundleId, TransitionBundle>,
+    extensions: BTreeMap<OpId, Extension>,
+    witnesses: BTreeMap<XWitnessId, SealWitness>,
+    attachments: BTreeMap<AttachId, MediumBlob>,
+    secret_seals: BTreeMap<XChain<SecretSeal>, XChain<GraphSeal>>,
+    type_system: TypeSystem,
+    identities: BTreeMap<Identity, TrustLevel>,
+    libs: BTreeMap<LibId, Lib>,
+    sigs: BTreeMap<ContentId, ContentSigs>,
+}
+
+impl SqliteStash {
+    fn empty(journal: Journal) -> Self {
+        Self {
+            journal,
+            persistence: None,
+            schemata: BTreeMap::new(),
+            ifaces: BTreeMap::new(),
+            geneses: BTreeMap::new(),
+            suppl: BTreeMap::new(),
+            bundles: BTreeMap::new(),
+            extensions: BTreeMap::new(),
+            witnesses: BTreeMap::new(),
+            attachments: BTreeMap::new(),
+            secret_seals: BTreeMap::new(),
+            type_system: TypeSystem::new(),
+            identities: BTreeMap::new(),
+            libs: BTreeMap::new(),
+            sigs: BTreeMap::new(),
+        }
+    }
+
+    fn load(journal: Journal) -> Result<Self, SqliteError> {
+        let mut stash = Self::empty(journal);
+        let journal = &stash.journal;
+
+        for (_, value) in journal.rows("maps", STASH_SCHEMA)? {
+            let schema: Schema = decode(&value)?;
+            stash
+                .schemata
+                .insert(schema.schema_id(), SchemaIfaces::new(schema));
+        }
+        for (_, value) in journal.rows("maps", STASH_IFACE)? {
+            let iface: Iface = decode(&value)?;
+            stash.ifaces.insert(iface.iface_id(), iface);
+        }
+        let iimpls = journal.rows("maps", STASH_IIMPL)?;
+        for (key, value) in journal.rows("maps", STASH_TRUST)? {
+            stash.identities.insert(decode(&key)?, decode(&value)?);
+        }
+        for (_, value) in journal.rows("sets", STASH_SUPPL)? {
+            let suppl: Supplement = decode(&value)?;
+            stash
+                .suppl
+                .entry(suppl.content_id)
+                .or_default()
+                .insert(suppl);
+        }
+        for (_, value) in journal.rows("maps", STASH_GENESIS)? {
+            let genesis: Genesis = decode(&value)?;
+            stash.geneses.insert(genesis.contract_id(), genesis);
+        }
+        for (_, value) in journal.rows("maps", STASH_EXTENSION)? {
+            let extension: Extension = decode(&value)?;
+            stash.extensions.insert(extension.id(), extension);
+        }
+        for (_, value) in journal.rows("maps", STASH_BUNDLE)? {
+            let bundle: TransitionBundle = decode(&value)?;
+            stash.bundles.insert(bundle.bundle_id(), bundle);
+        }
+        for (_, value) in journal.rows("maps", STASH_WITNESS)? {
+            let witness: SealWitness = decode(&value)?;
+            stash.witnesses.insert(witness.witness_id(), witness);
+        }
+        for (key, value) in journal.rows("maps", STASH_ATTACHMENT)? {
+            stash.attachments.insert(decode(&key)?, decode(&value)?);
+        }
+        if let Some((_, value)) = journal.rows("maps", STASH_TYPES)?.pop() {
+            stash.type_system = decode(&value)?;
+        }
+        for (_, value) in journal.rows("maps", STASH_LIB)? {
+            let lib: Lib = decode(&value)?;
+            stash.libs.insert(lib.id(), lib);
+        }
+        for (key, value) in journal.rows("maps", STASH_SIGS)? {
+            stash.sigs.insert(decode(&key)?, decode(&value)?);
+        }
+        for (_, value) in journal.rows("sets", STASH_SECRET_SEAL)? {
+            let seal: XChain<GraphSeal> = decode(&value)?;
+            stash.secret_seals.insert(seal.conceal(), seal);
+        }
+
+        // Interfaces must be known to register their implementations.
+        for (_, value) in iimpls {
+            stash.insert_iimpl(decode(&value)?)?;
+        }
+
+        Ok(stash)
+    }
+
+    fn reload(&mut self) -> Result<(), SqliteError> {
+        let persistence = self.persistence.take();
+        *self = Self::load(self.journal.clone())?;
+        self.persistence = persistence;
+        Ok(())
+    }
+
+    // Drops the changes of a rolled back transaction.
+    fn refresh(&mut self) -> Result<(), SqliteError> {
+        if self.journal.is_stale() {
+            if self.journal.dirty {
+                self.reload()?;
+            }
+            self.journal.synced();
+        }
+        Ok(())
+    }
+
+    fn insert_iimpl(&mut self, iimpl: IfaceImpl) -> Result<bool, SqliteError> {
+        let schema_id = iimpl.schema_id;
+        let Some(schema_ifaces) = self.schemata.get_mut(&schema_id) else {
+            return Err(SqliteError::InvalidData(format!(
+                "implementation of unknown schema {schema_id}"
+            )));
+        };
+        let Some(iface) = self.ifaces.get(&iimpl.iface_id) else {
+            return Err(SqliteError::InvalidData(format!(
+                "implementation of unknown interface {}",
+                iimpl.iface_id
+            )));
+        };
+        let iface_name = iface.name.clone();
+        let present = schema_ifaces.iimpls.contains_key(&iface_name);
+        schema_ifaces.iimpls.insert(iface_name, iimpl)?;
+        Ok(!present)
+    }
+}
+
+impl CloneNoPersistence for SqliteStash {
+    fn clone_no_persistence(&self) -> Self {
+        Self {
+            journal: self.journal.detached(),
+            persistence: None,
+            schemata: self.schemata.clone(),
+            ifaces: self.ifaces.clone(),
+            geneses: self.geneses.clone(),
+            suppl: self.suppl.clone(),
+            bundles: self.bundles.clone(),
+            extensions: self.extensions.clone(),
+            witnesses: self.witnesses.clone(),
+            attachments: self.attachments.clone(),
+            secret_seals: self.secret_seals.clone(),
+            type_system: self.type_system.clone(),
+            identities: self.identities.clone(),
+            libs: self.libs.clone(),
+            sigs: self.sigs.clone(),
+        }
+    }
+}
+
+impl Persisting for SqliteStash {
+    fn persistence(&self) -> Option<&Persistence<Self>> {
+        self.persistence.as_ref()
+    }
+
+    fn persistence_mut(&mut self) -> Option<&mut Persistence<Self>> {
+        self.persistence.as_mut()
+    }
+
+    fn as_mut_persistence(&mut self) -> &mut Option<Persistence<Self>> {
+        &mut self.persistence
+    }
+}
+
+impl StoreTransaction for SqliteStash {
+    type TransactionErr = SqliteError;
+
+    fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal.begin(STASH)?;
+        self.refresh()?;
+        // Whatever was written before is committed by now.
+        self.journal.dirty = false;
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal.commit(STASH)
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.journal.rollback();
+        self.refresh().expect("unable to reload the stash");
+    }
+}
+
+impl StashProvider for SqliteStash {}
+
+impl StashReadProvider for SqliteStash {
+    // Everything is read from memory.
+    type Error = Infallible;
+
+    fn type_system(&self) -> Result<&TypeSystem, Self::Error> {
+        Ok(&self.type_system)
+    }
+
+    fn lib(&self, id: LibId) -> Result<&Lib, StashProviderError<Self::Error>> {
+        self.libs
+            .get(&id)
+            .ok_or_else(|| StashInconsistency::LibAbsent(id).into())
+    }
+
+    fn ifaces(&self) -> Result<impl Iterator<Item = &Iface>, Self::Error> {
+        Ok(self.ifaces.values())
+    }
+
+    fn iface(&self, iface: impl Into<IfaceRef>) -> Result<&Iface, StashProviderError<Self::Error>> {
+        let iref = iface.into();
+        match iref {
+            IfaceRef::Name(ref name) => self.ifaces.values().find(|iface| &iface.name == name),
+            IfaceRef::Id(ref id) => self.ifaces.get(id),
+        }
+        .ok_or_else(|| StashInconsistency::IfaceAbsent(iref).into())
+    }
+
+    fn schemata(&self) -> Result<impl Iterator<Item = &SchemaIfaces>, Self::Error> {
+        Ok(self.schemata.values())
+    }
+
+    fn schemata_by<C: IfaceClass>(
+        &self,
+    ) -> Result<impl Iterator<Item = &SchemaIfaces>, Self::Error> {
+        Ok(self
+            .schemata
+            .values()
+            .filter(|schema_ifaces| self.impl_for::<C>(schema_ifaces).is_ok()))
+    }
+
+    fn impl_for<'a, C: IfaceClass + 'a>(
+        &'a self,
+        schema_ifaces: &'a SchemaIfaces,
+    ) -> Result<&'a IfaceImpl, StashProviderError<Self::Error>> {
+        schema_ifaces
+            .iimpls
+            .values()
+            .find(|iimpl| C::IFACE_IDS.contains(&iimpl.iface_id))
+            .or_else(|| {
+                C::IFACE_IDS.iter().find_map(|id| {
+                    let iface = self.iface(*id).ok()?;
+                    iface.find_abstractable_impl(schema_ifaces)
+                })
+            })
+            .ok_or_else(move || {
+                ContractIfaceError::NoAbstractImpl(
+                    C::IFACE_IDS[0],
+                    schema_ifaces.schema.schema_id(),
+                )
+                .into()
+            })
+    }
+
+    fn schema(
+        &self,
+        schema_id: SchemaId,
+    ) -> Result<&SchemaIfaces, StashProviderError<Self::Error>> {
+        self.schemata
+            .get(&schema_id)
+            .ok_or_else(|| StashInconsistency::SchemaAbsent(schema_id).into())
+    }
+
+    fn get_trust(&self, identity: &Identity) -> Result<TrustLevel, Self::Error> {
+        Ok(self.identities.get(identity).copied().unwrap_or_default())
+    }
+
+    fn supplement(&self, content_ref: ContentRef) -> Result<Option<&Supplement>, Self::Error> {
+        Ok(self.suppl.get(&content_ref).and_then(|s| s.first()))
+    }
+
+    fn supplements(
+        &self,
+        content_ref: ContentRef,
+    ) -> Result<impl Iterator<Item = Supplement>, Self::Error> {
+        Ok(self
+            .suppl
+            .get(&content_ref)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter())
+    }
+
+    fn sigs_for(&self, content_id: &ContentId) -> Result<Option<&ContentSigs>, Self::Error> {
+        Ok(self.sigs.get(content_id))
+    }
+
+    fn geneses(&self) -> Result<impl Iterator<Item = &Genesis>, Self::Error> {
+        Ok(self.geneses.values())
+    }
+
+    fn geneses_by<C: IfaceClass>(&self) -> Result<impl Iterator<Item = &Genesis>, Self::Error> {
+        Ok(self.schemata_by::<C>()?.flat_map(|schema_ifaces| {
+            self.geneses
+                .values()
+                .filter(|genesis| schema_ifaces.schema.schema_id() == genesis.schema_id)
+        }))
+    }
+
+    fn genesis(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<&Genesis, StashProviderError<Self::Error>> {
+        self.geneses
+            .get(&contract_id)
+            .ok_or(StashInconsistency::ContractAbsent(contract_id).into())
+    }
+
+    fn witness_ids(&self) -> Result<impl Iterator<Item = XWitnessId>, Self::Error> {
+        Ok(self.witnesses.keys().copied())
+    }
+
+    fn bundle_ids(&self) -> Result<impl Iterator<Item = BundleId>, Self::Error> {
+        Ok(self.bundles.keys().copied())
+    }
+
+    fn bundle(
+        &self,
+        bundle_id: BundleId,
+    ) -> Result<&TransitionBundle, StashProviderError<Self::Error>> {
+        self.bundles
+            .get(&bundle_id)
+            .ok_or(StashInconsistency::BundleAbsent(bundle_id).into())
+    }
+
+    fn extension_ids(&self) -> Result<impl Iterator<Item = OpId>, Self::Error> {
+        Ok(self.extensions.keys().copied())
+    }
+
+    fn extension(&self, op_id: OpId) -> Result<&Extension, StashProviderError<Self::Error>> {
+        self.extensions
+            .get(&op_id)
+            .ok_or(StashInconsistency::OperationAbsent(op_id).into())
+    }
+
+    fn witness(
+        &self,
+        witness_id: XWitnessId,
+    ) -> Result<&SealWitness, StashProviderError<Self::Error>> {
+        self.witnesses
+            .get(&witness_id)
+            .ok_or(StashInconsistency::WitnessAbsent(witness_id).into())
+    }
+
+    fn taprets(&self) -> Result<impl Iterator<Item = (XWitnessId, TapretCommitment)>, Self::Error> {
+        Ok(self
+            .witnesses
+            .iter()
+            .filter_map(|(witness_id, witness)| match &witness.anchors {
+                AnchorSet::Tapret(anchor)
+                | AnchorSet::Double {
+                    tapret: anchor,
+                    opret: _,
+                } => Some((
+                    *witness_id,
+                    TapretCommitment {
+                        mpc: anchor.mpc_proof.commit_id(),
+                        nonce: anchor.dbc_proof.path_proof.nonce(),
+                    },
+                )),
+                _ => None,
+            }))
+    }
+
+    fn seal_secret(
+        &self,
+        secret: XChain<SecretSeal>,
+    ) -> Result<Option<XChain<GraphSeal>>, Self::Error> {
+        Ok(self.secret_seals.get(&secret).copied())
+    }
+
+    fn secret_seals(&self) -> Result<impl Iterator<Item = XChain<GraphSeal>>, Self::Error> {
+        Ok(self.secret_seals.values().copied())
+    }
+}
+
+impl StashWriteProvider for SqliteStash {
+    type Error = SqliteError;
+
+    fn replace_schema(&mut self, schema: Schema) -> Result<bool, Self::Error> {
+        let schema_id = schema.schema_id();
+        if self.schemata.contains_key(&schema_id) {
+            return Ok(false);
+        }
+        self.journal
+            .put(STASH_SCHEMA, &encode(&schema_id), &encode(&schema))?;
+        self.schemata.insert(schema_id, SchemaIfaces::new(schema));
+        Ok(true)
+    }
+
+    fn replace_iface(&mut self, iface: Iface) -> Result<bool, Self::Error> {
+        let iface_id = iface.iface_id();
+        if self.ifaces.contains_key(&iface_id) {
+            return Ok(false);
+        }
+        self.journal
+            .put(STASH_IFACE, &encode(&iface_id), &encode(&iface))?;
+        self.ifaces.insert(iface_id, iface);
+        Ok(true)
+    }
+
+    fn replace_iimpl(&mut self, iimpl: IfaceImpl) -> Result<bool, Self::Error> {
+        let key = [encode(&iimpl.schema_id), encode(&iimpl.iface_id)].concat();
+        self.journal.put(STASH_IIMPL, &key, &encode(&iimpl))?;
+        self.insert_iimpl(iimpl)
+    }
+
+    /// # Panics
+    ///
+    /// If the database can't be written, since the trait only allows
+    /// confinement errors here.
+    fn set_trust(
+        &mut self,
+        identity: Identity,
+        trust: TrustLevel,
+    ) -> Result<(), confinement::Error> {
+        self.journal
+            .put(STASH_TRUST, &encode(&identity), &encode(&trust))
+            .expect("unable to write to the stash database");
+        self.identities.insert(identity, trust);
+        Ok(())
+    }
+
+    fn add_supplement(&mut self, suppl: Supplement) -> Result<(), Self::Error> {
+        self.journal
+            .add(STASH_SUPPL, &encode(&suppl.content_id), &encode(&suppl))?;
+        self.suppl
+            .entry(suppl.content_id)
+            .or_default()
+            .insert(suppl);
+        Ok(())
+    }
+
+    fn replace_genesis(&mut self, genesis: Genesis) -> Result<bool, Self::Error> {
+        let contract_id = genesis.contract_id();
+        self.journal
+            .put(STASH_GENESIS, &encode(&contract_id), &encode(&genesis))?;
+        let present = self.geneses.insert(contract_id, genesis).is_some();
+        Ok(!present)
+    }
+
+    fn replace_extension(&mut self, extension: Extension) -> Result<bool, Self::Error> {
+        let opid = extension.id();
+        self.journal
+            .put(STASH_EXTENSION, &encode(&opid), &encode(&extension))?;
+        let present = self.extensions.insert(opid, extension).is_some();
+        Ok(!present)
+    }
+
+    fn replace_bundle(&mut self, bundle: TransitionBundle) -> Result<bool, Self::Error> {
+        let bundle_id = bundle.bundle_id();
+        self.journal
+            .put(STASH_BUNDLE, &encode(&bundle_id), &encode(&bundle))?;
+        let present = self.bundles.insert(bundle_id, bundle).is_some();
+        Ok(!present)
+    }
+
+    fn replace_witness(&mut self, witness: SealWitness) -> Result<bool, Self::Error> {
+        let witness_id = witness.witness_id();
+        self.journal
+            .put(STASH_WITNESS, &encode(&witness_id), &encode(&witness))?;
+        let present = self.witnesses.insert(witness_id, witness).is_some();
+        Ok(!present)
+    }
+
+    fn replace_attachment(
+        &mut self,
+        id: AttachId,
+        attach: MediumBlob,
+    ) -> Result<bool, Self::Error> {
+        self.journal
+            .put(STASH_ATTACHMENT, &encode(&id), &encode(&attach))?;
+        let present = self.attachments.insert(id, attach).is_some();
+        Ok(!present)
+    }
+
+    fn consume_types(&mut self, types: TypeSystem) -> Result<(), Self::Error> {
+        self.type_system.extend(types)?;
+        self.journal
+            .put(STASH_TYPES, &[], &encode(&self.type_system))
+    }
+
+    fn replace_lib(&mut self, lib: Lib) -> Result<bool, Self::Error> {
+        let id = lib.id();
+        self.journal.put(STASH_LIB, &encode(&id), &encode(&lib))?;
+        let present = self.libs.insert(id, lib).is_some();
+        Ok(!present)
+    }
+
+    fn import_sigs<I>(&mut self, content_id: ContentId, sigs: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = (Identity, SigBlob)>,
+    {
+        let mut accepted = vec![];
+        for (identity, sig) in sigs {
+            let level = match self.identities.get(&identity) {
+                Some(level) => *level,
+                None => {
+                    let level = TrustLevel::default();
+                    self.journal
+                        .put(STASH_TRUST, &encode(&identity), &encode(&level))?;
+                    self.identities.insert(identity.clone(), level);
+                    level
+                }
+            };
+            if level.should_accept() {
+                accepted.push((identity, sig));
+            }
+        }
+
+        let sigs = match self.sigs.remove(&content_id) {
+            Some(mut prev_sigs) => {
+                prev_sigs.extend(accepted)?;
+                prev_sigs
+            }
+            None => ContentSigs::from(Confined::try_from_iter(accepted)?),
+        };
+        self.journal
+            .put(STASH_SIGS, &encode(&content_id), &encode(&sigs))?;
+        self.sigs.insert(content_id, sigs);
+        Ok(())
+    }
+
+    fn add_secret_seal(&mut self, seal: XChain<GraphSeal>) -> Result<bool, Self::Error> {
+        self.journal.add(STASH_SECRET_SEAL, &[], &encode(&seal))?;
+        let present = self.secret_seals.insert(seal.conceal(), seal).is_some();
+        Ok(!present)
+    }
+}
+
+//////////
+// STATE
+//////////
+
+#[derive(Clone, Debug)]
+enum StateOp {
+    Genesis(Genesis),
+    Transition(Transition, XWitnessId),
+    Extension(Extension, XWitnessId),
+}
+
+impl StateOp {
+    fn id(&self) -> (OpId, Option<XWitnessId>) {
+        match self {
+            Self::Genesis(genesis) => (genesis.id(), None),
+            Self::Transition(transition, witness_id) => (transition.id(), Some(*witness_id)),
+            Self::Extension(extension, witness_id) => (extension.id(), Some(*witness_id)),
+        }
+    }
+
+    fn ord_ref<'a>(&'a self, witnesses: &BTreeMap<XWitnessId, WitnessOrd>) -> OrdOpRef<'a> {
+        let ord = |witness_id| {
+            *witnesses
+                .get(witness_id)
+                .expect("witness ord is stored along with the operation")
+        };
+        match self {
+            Self::Genesis(genesis) => OrdOpRef::Genesis(genesis),
+            Self::Transition(transition, witness_id) => {
+                OrdOpRef::Transition(transition, *witness_id, ord(witness_id))
+            }
+            Self::Extension(extension, witness_id) => {
+                OrdOpRef::Extension(extension, *witness_id, ord(witness_id))
+            }
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Genesis(genesis) => [vec![0], encode(genesis)].concat(),
+            Self::Transition(transition, witness_id) => {
+                [vec![1], encode(transition), encode(witness_id)].concat()
+            }
+            Self::Extension(extension, witness_id) => {
+                [vec![2], encode(extension), encode(witness_id)].concat()
+            }
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, SqliteError> {
+        let Some((tag, data)) = data.split_first() else {
+            return Err(SqliteError::InvalidData("empty operation".to_string()));
+        };
+        let mut reader = StrictReader::in_memory::<U32MAX>(data);
+        Ok(match tag {
+            0 => Self::Genesis(read(&mut reader)?),
+            1 => Self::Transition(read(&mut reader)?, read(&mut reader)?),
+            2 => Self::Extension(read(&mut reader)?, read(&mut reader)?),
+            _ => return Err(SqliteError::InvalidData(format!("unknown operation {tag}"))),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ContractEntry {
+    schema: Schema,
+    ops: Vec<StateOp>,
+    known: BTreeSet<(OpId, Option<XWitnessId>)>,
+    // Position in `ops` of an operation of each witness.
+    witness_ops: BTreeMap<XWitnessId, usize>,
+    state: MemContract<MemContractState>,
+}
+
+impl ContractEntry {
+    fn new(schema: Schema, contract_id: ContractId) -> Self {
+        let state = MemContract::init((&schema, contract_id));
+        Self {
+            schema,
+            ops: vec![],
+            known: BTreeSet::new(),
+            witness_ops: BTreeMap::new(),
+            state,
+        }
+    }
+
+    /// Returns whether the operation wasn't known yet.
+    fn push(
+        &mut self,
+        op: StateOp,
+        witnesses: &BTreeMap<XWitnessId, WitnessOrd>,
+    ) -> Result<bool, SqliteError> {
+        if !self.known.insert(op.id()) {
+            return Ok(false);
+        }
+        self.state.evolve_state(op.ord_ref(witnesses))?;
+        if let (_, Some(witness_id)) = op.id() {
+            self.witness_ops.entry(witness_id).or_insert(self.ops.len());
+        }
+        self.ops.push(op);
+        Ok(true)
+    }
+
+    // The state keeps witness ords apart from the assignments, which don't
+    // depend on them, and only sets them when an operation is added. Adding
+    // an operation of the witness once more updates its ord, without
+    // replaying the whole history.
+    fn update_witness(
+        &mut self,
+        witness_id: XWitnessId,
+        witnesses: &BTreeMap<XWitnessId, WitnessOrd>,
+    ) -> Result<(), SqliteError> {
+        if let Some(pos) = self.witness_ops.get(&witness_id) {
+            self.state.evolve_state(self.ops[*pos].ord_ref(witnesses))?;
+        }
+        Ok(())
+    }
+
+    fn rebuild(
+        &self,
+        contract_id: ContractId,
+        witnesses: &BTreeMap<XWitnessId, WitnessOrd>,
+    ) -> Result<Self, SqliteError> {
+        let mut entry = Self::new(self.schema.clone(), contract_id);
+        for op in &self.ops {
+            entry.push(op.clone(), witnesses)?;
+        }
+        Ok(entry)
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteState {
+    journal: Journal,
+    persistence: Option<Persistence<Self>>,
+
+    witnesses: BTreeMap<XWitnessId, WitnessOrd>,
+    witness_contracts: BTreeMap<XWitnessId, BTreeSet<ContractId>>,
+    contracts: BTreeMap<ContractId, ContractEntry>,
+
+    // Changed since the last transaction began, and to be restored from the
+    // database if it's rolled back.
+    changed_witnesses: BTreeSet<XWitnessId>,
+    changed_contracts: BTreeSet<ContractId>,
+}
+
+impl SqliteState {
+    // The contract states can't be stored as they are, so the operations are
+    // replayed once, when the database is opened.
+    fn load(journal: Journal) -> Result<Self, SqliteError> {
+        let mut state = Self {
+            journal,
+            persistence: None,
+            witnesses: BTreeMap::new(),
+            witness_contracts: BTreeMap::new(),
+            contracts: BTreeMap::new(),
+            changed_witnesses: BTreeSet::new(),
+            changed_contracts: BTreeSet::new(),
+        };
+        let journal = state.journal.clone();
+
+        for (key, value) in journal.rows("maps", STATE_WITNESS)? {
+            state.witnesses.insert(decode(&key)?, decode(&value)?);
+        }
+        for (key, value) in journal.rows("maps", STATE_CONTRACT)? {
+            let contract_id = decode(&key)?;
+            let entry = ContractEntry::new(decode(&value)?, contract_id);
+            state.contracts.insert(contract_id, entry);
+        }
+        for (key, value) in journal.rows("log", STATE_OP)? {
+            state.load_op(decode(&key)?, StateOp::decode(&value)?)?;
+        }
+
+        Ok(state)
+    }
+
+    fn load_op(&mut self, contract_id: ContractId, op: StateOp) -> Result<(), SqliteError> {
+        if let (_, Some(witness_id)) = op.id() {
+            self.witness_contracts
+                .entry(witness_id)
+                .or_default()
+                .insert(contract_id);
+        }
+        self.contracts
+            .get_mut(&contract_id)
+            .ok_or_else(|| {
+                SqliteError::InvalidData(format!("operation of unknown contract {contract_id}"))
+            })?
+            .push(op, &self.witnesses)?;
+        Ok(())
+    }
+
+    // Drops the changes of a rolled back transaction. Only the contracts it
+    // changed are reloaded, the other ones just get back the ords of their
+    // witnesses.
+    fn refresh(&mut self) -> Result<(), SqliteError> {
+        if !self.journal.is_stale() {
+            return Ok(());
+        }
+
+        let witness_ids = mem::take(&mut self.changed_witnesses);
+        for witness_id in &witness_ids {
+            match self
+                .journal
+                .values("maps", STATE_WITNESS, &encode(witness_id))?
+                .pop()
+            {
+                Some(ord) => self.witnesses.insert(*witness_id, decode(&ord)?),
+                None => self.witnesses.remove(witness_id),
+            };
+        }
+
+        for contract_id in mem::take(&mut self.changed_contracts) {
+            self.contracts.remove(&contract_id);
+            for contract_ids in self.witness_contracts.values_mut() {
+                contract_ids.remove(&contract_id);
+            }
+
+            let key = encode(&contract_id);
+            let Some(schema) = self.journal.values("maps", STATE_CONTRACT, &key)?.pop() else {
+                continue;
+            };
+            let entry = ContractEntry::new(decode(&schema)?, contract_id);
+            self.contracts.insert(contract_id, entry);
+            for value in self.journal.values("log", STATE_OP, &key)? {
+                self.load_op(contract_id, StateOp::decode(&value)?)?;
+            }
+        }
+        self.witness_contracts.retain(|_, contract_ids| !contract_ids.is_empty());
+
+        for witness_id in witness_ids {
+            if self.witnesses.contains_key(&witness_id) {
+                self.update_witness_contracts(witness_id)?;
+            }
+        }
+
+        self.journal.synced();
+        Ok(())
+    }
+
+    fn set_witness_ord(
+        &mut self,
+        witness_id: XWitnessId,
+        ord: WitnessOrd,
+    ) -> Result<bool, SqliteError> {
+        if self.witnesses.get(&witness_id) == Some(&ord) {
+            return Ok(false);
+        }
+        self.journal
+            .put(STATE_WITNESS, &encode(&witness_id), &encode(&ord))?;
+        self.witnesses.insert(witness_id, ord);
+        self.changed_witnesses.insert(witness_id);
+        Ok(true)
+    }
+
+    fn update_witness_contracts(&mut self, witness_id: XWitnessId) -> Result<(), SqliteError> {
+        let Some(contract_ids) = self.witness_contracts.get(&witness_id) else {
+            return Ok(());
+        };
+        for contract_id in contract_ids {
+            self.contracts
+                .get_mut(contract_id)
+                .expect("witnesses are indexed for known contracts")
+                .update_witness(witness_id, &self.witnesses)?;
+        }
+        Ok(())
+    }
+
+    fn add_op(
+        &mut self,
+        contract_id: ContractId,
+        op: StateOp,
+        ord: Option<WitnessOrd>,
+    ) -> Result<(), SqliteError> {
+        // Same as `MemState`, the latest known ord of a witness replaces the
+        // previous one for all the contracts.
+        let mut changed = None;
+        if let ((_, Some(witness_id)), Some(ord)) = (op.id(), ord) {
+            if self.set_witness_ord(witness_id, ord)? {
+                changed = Some(witness_id);
+            }
+        }
+
+        let value = op.encode();
+        let witness_id = op.id().1;
+        let entry = self
+            .contracts
+            .get_mut(&contract_id)
+            .expect("writers are created for known contracts");
+        if entry.push(op, &self.witnesses)? {
+            self.changed_contracts.insert(contract_id);
+            self.journal
+                .append(STATE_OP, &encode(&contract_id), &value)?;
+        }
+        if let Some(witness_id) = witness_id {
+            self.witness_contracts
+                .entry(witness_id)
+                .or_default()
+                .insert(contract_id);
+        }
+
+        if let Some(witness_id) = changed {
+            self.update_witness_contracts(witness_id)?;
+        }
+        Ok(())
+    }
+
+    fn refresh_witnesses(
+        &mut self,
+        resolver: impl ResolveWitness,
+        after_height: u32,
+    ) -> Result<UpdateRes, SqliteError> {
+        let after_height = NonZeroU32::new(after_height).unwrap_or(NonZeroU32::MIN);
+        let witness_ids = self
+            .witnesses
+            .iter()
+            .filter(|(_, ord)| !matches!(ord, WitnessOrd::Mined(pos) if pos.height() < after_height))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        let mut succeeded = 0;
+        let mut failed = HashMap::new();
+        for witness_id in witness_ids {
+            match resolver.resolve_pub_witness_ord(witness_id) {
+                Ok(ord) => {
+                    succeeded += 1;
+                    if self.set_witness_ord(witness_id, ord)? {
+                        self.update_witness_contracts(witness_id)?;
+                    }
+                }
+                Err(err) => {
+                    failed.insert(witness_id, err.to_string());
+                }
+            }
+        }
+        Ok(UpdateRes { succeeded, failed })
+    }
+}
+
+impl CloneNoPersistence for SqliteState {
+    fn clone_no_persistence(&self) -> Self {
+        Self {
+            journal: self.journal.detached(),
+            persistence: None,
+            witnesses: self.witnesses.clone(),
+            witness_contracts: self.witness_contracts.clone(),
+            changed_witnesses: BTreeSet::new(),
+            changed_contracts: BTreeSet::new(),
+            contracts: self
+                .contracts
+                .iter()
+                .map(|(contract_id, entry)| {
+                    let entry = entry
+                        .rebuild(*contract_id, &self.witnesses)
+                        .expect("the state was already built once");
+                    (*contract_id, entry)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Persisting for SqliteState {
+    fn persistence(&self) -> Option<&Persistence<Self>> {
+        self.persistence.as_ref()
+    }
+
+    fn persistence_mut(&mut self) -> Option<&mut Persistence<Self>> {
+        self.persistence.as_mut()
+    }
+
+    fn as_mut_persistence(&mut self) -> &mut Option<Persistence<Self>> {
+        &mut self.persistence
+    }
+}
+
+impl StoreTransaction for SqliteState {
+    type TransactionErr = SqliteError;
+
+    fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal.begin(STATE)?;
+        self.refresh()?;
+        // Whatever was changed before is committed by now.
+        self.changed_witnesses.clear();
+        self.changed_contracts.clear();
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal.commit(STATE)
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.journal.rollback();
+        self.refresh().expect("unable to reload the state");
+    }
+}
+
+impl StateProvider for SqliteState {}
+
+impl StateReadProvider for SqliteState {
+    type ContractRead<'a> = SqliteContract<'a>;
+    type Error = StateInconsistency;
+
+    fn contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<Self::ContractRead<'_>, Self::Error> {
+        self.contracts
+            .get(&contract_id)
+            .map(|entry| SqliteContract(&entry.state))
+            .ok_or(StateInconsistency::UnknownContract(contract_id))
+    }
+
+    fn is_valid_witness(&self, witness_id: XWitnessId) -> Result<bool, Self::Error> {
+        let ord = self
+            .witnesses
+            .get(&witness_id)
+            .ok_or(StateInconsistency::AbsentWitness(witness_id))?;
+        Ok(ord.is_valid())
+    }
+}
+
+impl StateWriteProvider for SqliteState {
+    type ContractWrite<'a> = SqliteContractWriter<'a>;
+    type Error = SqliteError;
+
+    fn register_contract(
+        &mut self,
+        schema: &Schema,
+        genesis: &Genesis,
+    ) -> Result<Self::ContractWrite<'_>, Self::Error> {
+        let contract_id = genesis.contract_id();
+        if !self.contracts.contains_key(&contract_id) {
+            self.changed_contracts.insert(contract_id);
+            self.journal
+                .put(STATE_CONTRACT, &encode(&contract_id), &encode(schema))?;
+            self.contracts
+                .insert(contract_id, ContractEntry::new(schema.clone(), contract_id));
+        }
+        let mut writer = SqliteContractWriter {
+            state: self,
+            contract_id,
+        };
+        writer.add_genesis(genesis)?;
+        Ok(writer)
+    }
+
+    fn update_contract(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<Option<Self::ContractWrite<'_>>, Self::Error> {
+        if !self.contracts.contains_key(&contract_id) {
+            return Ok(None);
+        }
+        Ok(Some(SqliteContractWriter {
+            state: self,
+            contract_id,
+        }))
+    }
+
+    fn update_witnesses(
+        &mut self,
+        resolver: impl ResolveWitness,
+        after_height: u32,
+    ) -> Result<UpdateRes, Self::Error> {
+        self.begin_transaction()?;
+        match self.refresh_witnesses(resolver, after_height) {
+            Ok(res) => {
+                self.commit_transaction()?;
+                Ok(res)
+            }
+            Err(e) => {
+                self.rollback_transaction();
+                Err(e)
+            }
+        }
+    }
+}
+
+/// State of a contract, as known to `SqliteState`.
+#[derive(Debug)]
+pub struct SqliteContract<'a>(&'a MemContract<MemContractState>);
+
+impl ContractStateAccess for SqliteContract<'_> {
+    fn global(
+        &self,
+        ty: rgbstd::GlobalStateType,
+    ) -> Result<GlobalContractState<impl GlobalStateIter>, UnknownGlobalStateType> {
+        self.0.global(ty)
+    }
+
+    fn rights(&self, outpoint: XOutpoint, ty: AssignmentType) -> u32 {
+        self.0.rights(outpoint, ty)
+    }
+
+    fn fungible(
+        &self,
+        outpoint: XOutpoint,
+        ty: AssignmentType,
+    ) -> impl DoubleEndedIterator<Item = FungibleState> {
+        self.0.fungible(outpoint, ty)
+    }
+
+    fn data(
+        &self,
+        outpoint: XOutpoint,
+        ty: AssignmentType,
+    ) -> impl DoubleEndedIterator<Item = impl Borrow<DataState>> {
+        self.0.data(outpoint, ty)
+    }
+
+    fn attach(
+        &self,
+        outpoint: XOutpoint,
+        ty: AssignmentType,
+    ) -> impl DoubleEndedIterator<Item = impl Borrow<AttachState>> {
+        self.0.attach(outpoint, ty)
+    }
+}
+
+impl ContractStateRead for SqliteContract<'_> {
+    fn contract_id(&self) -> ContractId {
+        self.0.contract_id()
+    }
+
+    fn schema_id(&self) -> SchemaId {
+        self.0.schema_id()
+    }
+
+    fn witness_ord(&self, witness_id: XWitnessId) -> Option<WitnessOrd> {
+        self.0.witness_ord(witness_id)
+    }
+
+    fn rights_all(&self) -> impl Iterator<Item = &OutputAssignment<VoidState>> {
+        self.0.rights_all()
+    }
+
+    fn fungible_all(&self) -> impl Iterator<Item = &OutputAssignment<RevealedValue>> {
+        self.0.fungible_all()
+    }
+
+    fn data_all(&self) -> impl Iterator<Item = &OutputAssignment<RevealedData>> {
+        self.0.data_all()
+    }
+
+    fn attach_all(&self) -> impl Iterator<Item = &OutputAssignment<RevealedAttach>> {
+        self.0.attach_all()
+    }
+}
+
+pub struct SqliteContractWriter<'a> {
+    state: &'a mut SqliteState,
+    contract_id: ContractId,
+}
+
+impl ContractStateWrite for SqliteContractWriter<'_> {
+    type Error = SqliteError;
+
+    fn add_genesis(&mut self, genesis: &Genesis) -> Result<(), Self::Error> {
+        self.state
+            .add_op(self.contract_id, StateOp::Genesis(genesis.clone()), None)
+    }
+
+    fn add_transition(
+        &mut self,
+        transition: &Transition,
+        witness_id: XWitnessId,
+        ord: WitnessOrd,
+    ) -> Result<(), Self::Error> {
+        let op = StateOp::Transition(transition.clone(), witness_id);
+        self.state.add_op(self.contract_id, op, Some(ord))
+    }
+
+    fn add_extension(
+        &mut self,
+        extension: &Extension,
+        witness_id: XWitnessId,
+        ord: WitnessOrd,
+    ) -> Result<(), Self::Error> {
+        let op = StateOp::Extension(extension.clone(), witness_id);
+        self.state.add_op(self.contract_id, op, Some(ord))
+    }
+}
+
+//////////
+// INDEX
+//////////
+
+#[derive(Debug)]
+pub struct SqliteIndex {
+    journal: Journal,
+    persistence: Option<Persistence<Self>>,
+
+    op_bundle: BTreeMap<OpId, BundleId>,
+    bundle_contract: BTreeMap<BundleId, ContractId>,
+    bundle_witness: BTreeMap<BundleId, BTreeSet<XWitnessId>>,
+    contract_outputs: BTreeMap<ContractId, BTreeMap<XOutputSeal, BTreeSet<Opout>>>,
+    output_contracts: BTreeMap<XOutputSeal, BTreeSet<ContractId>>,
+    terminals: BTreeMap<XChain<SecretSeal>, BTreeSet<Opout>>,
+}
+
+impl SqliteIndex {
+    fn load(journal: Journal) -> Result<Self, SqliteError> {
+        let mut index = Self {
+            journal,
+            persistence: None,
+            op_bundle: BTreeMap::new(),
+            bundle_contract: BTreeMap::new(),
+            bundle_witness: BTreeMap::new(),
+            contract_outputs: BTreeMap::new(),
+            output_contracts: BTreeMap::new(),
+            terminals: BTreeMap::new(),
+        };
+        let journal = index.journal.clone();
+
+        for (key, _) in journal.rows("maps", INDEX_CONTRACT)? {
+            index.contract_outputs.insert(decode(&key)?, BTreeMap::new());
+        }
+        for (key, value) in journal.rows("maps", INDEX_OP_BUNDLE)? {
+            index.op_bundle.insert(decode(&key)?, decode(&value)?);
+        }
+        for (key, value) in journal.rows("maps", INDEX_BUNDLE_CONTRACT)? {
+            index.bundle_contract.insert(decode(&key)?, decode(&value)?);
+        }
+        for (key, value) in journal.rows("sets", INDEX_BUNDLE_WITNESS)? {
+            index
+                .bundle_witness
+                .entry(decode(&key)?)
+                .or_default()
+                .insert(decode(&value)?);
+        }
+        for (key, value) in journal.rows("sets", INDEX_OUTPUT_OPOUT)? {
+            let mut reader = StrictReader::in_memory::<U32MAX>(value.as_slice());
+            index.insert_output(decode(&key)?, read(&mut reader)?, read(&mut reader)?);
+        }
+        for (key, value) in journal.rows("sets", INDEX_TERMINAL)? {
+            index
+                .terminals
+                .entry(decode(&key)?)
+                .or_default()
+                .insert(decode(&value)?);
+        }
+
+        Ok(index)
+    }
+
+    fn reload(&mut self) -> Result<(), SqliteError> {
+        let persistence = self.persistence.take();
+        *self = Self::load(self.journal.clone())?;
+        self.persistence = persistence;
+        Ok(())
+    }
+
+    // Drops the changes of a rolled back transaction.
+    fn refresh(&mut self) -> Result<(), SqliteError> {
+        if self.journal.is_stale() {
+            if self.journal.dirty {
+                self.reload()?;
+            }
+            self.journal.synced();
+        }
+        Ok(())
+    }
+
+    fn insert_output(&mut self, contract_id: ContractId, output: XOutputSeal, opout: Opout) {
+        self.contract_outputs
+            .entry(contract_id)
+            .or_default()
+            .entry(output)
+            .or_default()
+            .insert(opout);
+        self.output_contracts
+            .entry(output)
+            .or_default()
+            .insert(contract_id);
+    }
+
+    fn add_output(
+        &mut self,
+        contract_id: ContractId,
+        output: XOutputSeal,
+        opout: Opout,
+    ) -> Result<(), IndexWriteError<SqliteError>> {
+        self.journal
+            .add(
+                INDEX_OUTPUT_OPOUT,
+                &encode(&contract_id),
+                &[encode(&output), encode(&opout)].concat(),
+            )
+            .map_err(IndexWriteError::Connectivity)?;
+        self.insert_output(contract_id, output, opout);
+        Ok(())
+    }
+
+    fn extend_terminals<State: ExposedState, Seal: ExposedSeal>(
+        &mut self,
+        vec: &[Assign<State, Seal>],
+        opid: OpId,
+        type_id: AssignmentType,
+    ) -> Result<(), IndexWriteError<SqliteError>> {
+        for (no, assign) in vec.iter().enumerate() {
+            let opout = Opout::new(opid, type_id, no as u16);
+            if let Assign::Confidential { seal, .. } | Assign::ConfidentialSeal { seal, .. } =
+                assign
+            {
+                self.journal
+                    .add(INDEX_TERMINAL, &encode(seal), &encode(&opout))
+                    .map_err(IndexWriteError::Connectivity)?;
+                self.terminals.entry(*seal).or_default().insert(opout);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CloneNoPersistence for SqliteIndex {
+    fn clone_no_persistence(&self) -> Self {
+        Self {
+            journal: self.journal.detached(),
+            persistence: None,
+            op_bundle: self.op_bundle.clone(),
+            bundle_contract: self.bundle_contract.clone(),
+            bundle_witness: self.bundle_witness.clone(),
+            contract_outputs: self.contract_outputs.clone(),
+            output_contracts: self.output_contracts.clone(),
+            terminals: self.terminals.clone(),
+        }
+    }
+}
+
+impl Persisting for SqliteIndex {
+    fn persistence(&self) -> Option<&Persistence<Self>> {
+        self.persistence.as_ref()
+    }
+
+    fn persistence_mut(&mut self) -> Option<&mut Persistence<Self>> {
+        self.persistence.as_mut()
+    }
+
+    fn as_mut_persistence(&mut self) -> &mut Option<Persistence<Self>> {
+        &mut self.persistence
+    }
+}
+
+impl StoreTransaction for SqliteIndex {
+    type TransactionErr = SqliteError;
+
+    fn begin_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal.begin(INDEX)?;
+        self.refresh()?;
+        // Whatever was written before is committed by now.
+        self.journal.dirty = false;
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<(), Self::TransactionErr> {
+        self.journal.commit(INDEX)
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.journal.rollback();
+        self.refresh().expect("unable to reload the index");
+    }
+}
+
+impl IndexProvider for SqliteIndex {}
+
+impl IndexReadProvider for SqliteIndex {
+    type Error = Infallible;
+
+    fn contracts_assigning(
+        &self,
+        outputs: BTreeSet<XOutputSeal>,
+    ) -> Result<impl Iterator<Item = ContractId> + '_, Self::Error> {
+        Ok(outputs.into_iter().flat_map(|output| {
+            self.output_contracts
+                .get(&output)
+                .into_iter()
+                .flatten()
+                .copied()
+        }))
+    }
+
+    fn public_opouts(
+        &self,
+        contract_id: ContractId,
+    ) -> Result<BTreeSet<Opout>, IndexReadError<Self::Error>> {
+        // Same as `MemIndex`, public opouts are never indexed.
+        if !self.contract_outputs.contains_key(&contract_id) {
+            return Err(IndexInconsistency::ContractAbsent(contract_id).into());
+        }
+        Ok(BTreeSet::new())
+    }
+
+    fn opouts_by_outputs(
+        &self,
+        contract_id: ContractId,
+        outputs: impl IntoIterator<Item = impl Into<XOutputSeal>>,
+    ) -> Result<BTreeSet<Opout>, IndexReadError<Self::Error>> {
+        let index = self
+            .contract_outputs
+            .get(&contract_id)
+            .ok_or(IndexInconsistency::ContractAbsent(contract_id))?;
+        let mut opouts = BTreeSet::new();
+        for output in outputs.into_iter().map(|o| o.into()) {
+            let set = index
+                .get(&output)
+                .ok_or(IndexInconsistency::OutpointUnknown(output, contract_id))?;
+            opouts.extend(set)
+        }
+        Ok(opouts)
+    }
+
+    fn opouts_by_terminals(
+        &self,
+        terminals: impl IntoIterator<Item = XChain<SecretSeal>>,
+    ) -> Result<BTreeSet<Opout>, Self::Error> {
+        Ok(terminals
+            .into_iter()
+            .filter_map(|seal| self.terminals.get(&seal))
+            .flatten()
+            .copied()
+            .collect())
+    }
+
+    fn bundle_id_for_op(&self, opid: OpId) -> Result<BundleId, IndexReadError<Self::Error>> {
+        self.op_bundle
+            .get(&opid)
+            .copied()
+            .ok_or(IndexInconsistency::BundleAbsent(opid).into())
+    }
+
+    fn bundle_info(
+        &self,
+        bundle_id: BundleId,
+    ) -> Result<(impl Iterator<Item = XWitnessId>, ContractId), IndexReadError<Self::Error>> {
+        let witness_ids = self
+            .bundle_witness
+            .get(&bundle_id)
+            .ok_or(IndexInconsistency::BundleWitnessUnknown(bundle_id))?;
+        let contract_id = self
+            .bundle_contract
+            .get(&bundle_id)
+            .ok_or(IndexInconsistency::BundleContractUnknown(bundle_id))?;
+        Ok((witness_ids.iter().copied(), *contract_id))
+    }
+}
+
+impl IndexWriteProvider for SqliteIndex {
+    type Error = SqliteError;
+
+    fn register_contract(&mut self, contract_id: ContractId) -> Result<bool, Self::Error> {
+        if self.contract_outputs.contains_key(&contract_id) {
+            return Ok(false);
+        }
+        self.journal
+            .put(INDEX_CONTRACT, &encode(&contract_id), &[])?;
+        self.contract_outputs.insert(contract_id, BTreeMap::new());
+        Ok(true)
+    }
+
+    fn register_bundle(
+        &mut self,
+        bundle_id: BundleId,
+        witness_id: XWitnessId,
+        contract_id: ContractId,
+    ) -> Result<bool, IndexWriteError<Self::Error>> {
+        if let Some(alt) = self
+            .bundle_contract
+            .get(&bundle_id)
+            .filter(|alt| *alt != &contract_id)
+        {
+            return Err(IndexInconsistency::DistinctBundleContract {
+                bundle_id,
+                present: *alt,
+                expected: contract_id,
+            }
+            .into());
+        }
+        let bundle_key = encode(&bundle_id);
+        self.journal
+            .add(INDEX_BUNDLE_WITNESS, &bundle_key, &encode(&witness_id))
+            .and_then(|_| {
+                self.journal
+                    .put(INDEX_BUNDLE_CONTRACT, &bundle_key, &encode(&contract_id))
+            })
+            .map_err(IndexWriteError::Connectivity)?;
+        self.bundle_witness
+            .entry(bundle_id)
+            .or_default()
+            .insert(witness_id);
+        let present = self
+            .bundle_contract
+            .insert(bundle_id, contract_id)
+            .is_some();
+        Ok(!present)
+    }
+
+    fn register_operation(
+        &mut self,
+        opid: OpId,
+        bundle_id: BundleId,
+    ) -> Result<bool, IndexWriteError<Self::Error>> {
+        if let Some(alt) = self
+            .op_bundle
+            .get(&opid)
+            .filter(|alt| *alt != &bundle_id)
+        {
+            return Err(IndexInconsistency::DistinctBundleOp {
+                opid,
+                present: *alt,
+                expected: bundle_id,
+            }
+            .into());
+        }
+        self.journal
+            .put(INDEX_OP_BUNDLE, &encode(&opid), &encode(&bundle_id))
+            .map_err(IndexWriteError::Connectivity)?;
+        let present = self.op_bundle.insert(opid, bundle_id).is_some();
+        Ok(!present)
+    }
+
+    fn index_genesis_assignments<State: ExposedState>(
+        &mut self,
+        contract_id: ContractId,
+        vec: &[Assign<State, GenesisSeal>],
+        opid: OpId,
+        type_id: AssignmentType,
+    ) -> Result<(), IndexWriteError<Self::Error>> {
+        if !self.contract_outputs.contains_key(&contract_id) {
+            return Err(IndexInconsistency::ContractAbsent(contract_id).into());
+        }
+
+        for (no, assign) in vec.iter().enumerate() {
+            let opout = Opout::new(opid, type_id, no as u16);
+            if let Assign::ConfidentialState { seal, .. } | Assign::Revealed { seal, .. } = assign {
+                let output = seal
+                    .to_output_seal()
+                    .expect("genesis seals always have outpoint");
+                self.add_output(contract_id, output, opout)?;
+            }
+        }
+
+        self.extend_terminals(vec, opid, type_id)
+    }
+
+    fn index_transition_assignments<State: ExposedState>(
+        &mut self,
+        contract_id: ContractId,
+        vec: &[Assign<State, GraphSeal>],
+        opid: OpId,
+        type_id: AssignmentType,
+        witness_id: XWitnessId,
+    ) -> Result<(), IndexWriteError<Self::Error>> {
+        if !self.contract_outputs.contains_key(&contract_id) {
+            return Err(IndexInconsistency::ContractAbsent(contract_id).into());
+        }
+
+        for (no, assign) in vec.iter().enumerate() {
+            let opout = Opout::new(opid, type_id, no as u16);
+            if let Assign::ConfidentialState { seal, .. } | Assign::Revealed { seal, .. } = assign {
+                let output = seal.try_to_output_seal(witness_id).unwrap_or_else(|_| {
+                    panic!(
+                        "chain mismatch between assignment vout seal ({seal}) and witness \
+                         transaction ({witness_id})"
+                    )
+                });
+                self.add_output(contract_id, output, opout)?;
+            }
+        }
+
+        self.extend_terminals(vec, opid, type_id)
+    }
+}
@@ -16,6 +16,7 @@ use crate::types::{
     RgbAssignments,
     Beneficiary,
     ContractId,
+    Network,
 };
 use crate::resolvers::LnResolver;
 use crate::ToRaw;
@@ -86,28 +87,39 @@ fn build_rgb_tx(inputs: &[Outpoint], outputs_num: usize, commitment: &[u8; 32])
 }
 
 fn get_stock() -> Stock {
-    // use rgbstd::persistence::fs::FsBinStore;
-    // use tempfile::tempdir;
     use schemata::NonInflatableAsset;
 
-    // let data_dir = tempdir().unwrap();
-    // let stock_path = data_dir.into_path();
-
-    // let provider = FsBinStore::new(stock_path.clone()).unwrap();
-    // Stock::load(provider, true).unwrap();
-
-    // let provider = FsBinStore::new(stock_path).unwrap();
     let mut stock = Stock::in_memory();
-    // stock.make_persistent(provider, true).unwrap();
-
     stock.import_kit(NonInflatableAsset::kit()).unwrap();
 
     stock
 }
 
+// Issues the "TEST" contract with 100 assigned to the first output of
+// `genesis_tx`.
+fn issue_test_contract(genesis_tx: &BpTx, precision: u8, network: Network) -> ValidContract {
+    let allocations = [(format!("opret1st:{}:0", genesis_tx.txid()), 100)];
+    rgb_issue("test", "TEST", "TestCoin", "For tests".into(), precision, allocations, network)
+}
+
+// The "TEST" contract imported into a fresh stock, along with a resolver
+// knowing its genesis tx.
+fn get_test_contract(network: Network) -> (BpTx, ContractId, LnResolver, Stock) {
+    let genesis_tx = get_first_tx();
+    let contract = issue_test_contract(&genesis_tx, 8, network);
+    let contract_id: ContractId = contract.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    stock.import_contract(contract, &resolver).unwrap();
+
+    (genesis_tx, contract_id, resolver, stock)
+}
+
 #[test]
 fn test_rgb_workflow() {
-    let is_testnet = true;
+    let network = Network::Regtest;
 
     let tx = get_first_tx();
     let txid = tx.txid();
@@ -121,7 +133,7 @@ fn test_rgb_workflow() {
     ];
 
     let contract = rgb_issue(
-        "test", "TEST", "TestCoin", "For tests".into(), 8, allocations, is_testnet,
+        "test", "TEST", "TestCoin", "For tests".into(), 8, allocations, network,
     );
     let contract_id: ContractId  = contract.contract_id().into();
     dbg!(&contract_id);
@@ -152,7 +164,14 @@ fn test_rgb_workflow() {
     }
 
     let coins = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
-    let ti_list = rgb_compose(&stock, dbg!(coins), rgb_assignments, Some(Beneficiary::WitnessVout(2)));
+    let ti_list = rgb_compose(
+        &stock,
+        dbg!(coins),
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(2)),
+        network,
+    )
+    .unwrap();
     // let ti_list = rgb_compose(&stock, dbg!(coins), rgb_assignments, None);
     let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
 
@@ -172,13 +191,13 @@ fn test_rgb_workflow() {
         // Outpoint::new(txid, 1),
         // Outpoint::new(txid, 2),
     ];
-    let consign = rgb_transfer(&stock, contract_id, &outputs, None);
+    let consign = rgb_transfer(&stock, contract_id, &outputs, None, network).unwrap();
     dbg!(&consign.consignment_id());
     // dbg!(&consign);
 
-    consign.validate(&resolver, is_testnet).unwrap();
+    consign.validate(&resolver, network.is_testnet()).unwrap();
 
-    dbg!(rgb_balance(&stock, contract_id, &outputs));
+    dbg!(rgb_balance(&stock, contract_id, &outputs, network).unwrap());
 
     // let available_utxos = [
     //     Outpoint::new(spending_txid, 0),
@@ -205,7 +224,7 @@ fn test_rgb_workflow() {
 
 #[test]
 fn test_coloring_consistency() {
-    let is_testnet = true;
+    let network = Network::Regtest;
 
     let genesis_tx = get_first_tx();
     let genesis_txid = genesis_tx.txid();
@@ -218,12 +237,12 @@ fn test_coloring_consistency() {
     ];
 
     let contract = rgb_issue(
-        "test", "TEST", "TestCoin", "For tests".into(), 8, allocations, is_testnet,
+        "test", "TEST", "TestCoin", "For tests".into(), 8, allocations, network,
     );
 
     for _ in 0..10 {
-        let (first_commitment, first_consignment) = basic_transfer(genesis_tx.clone(), contract.clone(), is_testnet);
-        let (second_commitment, second_consignment) = basic_transfer(genesis_tx.clone(), contract.clone(), is_testnet);
+        let (first_commitment, first_consignment) = basic_transfer(genesis_tx.clone(), contract.clone(), network);
+        let (second_commitment, second_consignment) = basic_transfer(genesis_tx.clone(), contract.clone(), network);
 
         assert_eq!(first_commitment, second_commitment);
         assert_eq!(first_consignment.consignment_id(), second_consignment.consignment_id());
@@ -233,7 +252,7 @@ fn test_coloring_consistency() {
 fn basic_transfer(
     genesis_tx: Tx,
     contract: ValidContract,
-    is_testnet: bool,
+    network: Network,
 ) -> ([u8; 32], ValidTransfer) {
     let genesis_txid = genesis_tx.txid();
     let contract_id: ContractId = contract.contract_id().into();
@@ -258,7 +277,14 @@ fn basic_transfer(
         Outpoint::new(genesis_txid, 0),
     ];
     let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
-    let ti_list = rgb_compose(&stock, prev_outputs, rgb_assignments, Some(Beneficiary::WitnessVout(2)));
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(2)),
+        network,
+    )
+    .unwrap();
     let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
 
     let spending_tx = build_rgb_tx(&available_utxos, 3, &commitment);
@@ -274,10 +300,10 @@ fn basic_transfer(
         // Outpoint::new(spending_txid, 1),
         // Outpoint::new(spending_txid, 2),
     ];
-    let transfer = rgb_transfer(&stock, contract_id, &outputs, None);
-    let valid_transfer = transfer.validate(&resolver, is_testnet).unwrap();
+    let transfer = rgb_transfer(&stock, contract_id, &outputs, None, network).unwrap();
+    let valid_transfer = transfer.validate(&resolver, network.is_testnet()).unwrap();
 
-    let balance = rgb_balance(&stock, contract_id, &outputs);
+    let balance = rgb_balance(&stock, contract_id, &outputs, network).unwrap();
     assert_eq!(balance, 20);
 
     {
@@ -286,16 +312,2204 @@ fn basic_transfer(
             Outpoint::new(spending_txid, 1),
             // Outpoint::new(spending_txid, 2),
         ];
-        let transfer = rgb_transfer(&stock, contract_id, &outputs, None);
-        let valid_transfer = transfer.validate(&resolver, is_testnet).unwrap();
+        let transfer = rgb_transfer(&stock, contract_id, &outputs, None, network).unwrap();
+        let valid_transfer = transfer.validate(&resolver, network.is_testnet()).unwrap();
 
         let mut stock = get_stock();
         stock.accept_transfer(valid_transfer.clone(), resolver).unwrap();
 
-        let balance = rgb_balance(&stock, contract_id, &outputs);
+        let balance = rgb_balance(&stock, contract_id, &outputs, network).unwrap();
 
         assert_eq!(balance, 80);
     }
 
     (commitment, valid_transfer)
 }
+
+#[test]
+fn test_chain_resolver() {
+    use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+    use rgbstd::vm::{WitnessOrd, WitnessPos, XWitnessTx};
+    use rgbstd::{XChain, XWitnessId};
+
+    use crate::resolvers::{ChainResolver, LocalResolver};
+
+    // Backend failing every request, like an unreachable server.
+    struct DownResolver;
+    impl ResolveWitness for DownResolver {
+        fn resolve_pub_witness(
+            &self,
+            witness_id: XWitnessId,
+        ) -> Result<XWitnessTx, WitnessResolverError> {
+            Err(WitnessResolverError::Other(witness_id, "down".to_string()))
+        }
+
+        fn resolve_pub_witness_ord(
+            &self,
+            witness_id: XWitnessId,
+        ) -> Result<WitnessOrd, WitnessResolverError> {
+            Err(WitnessResolverError::Other(witness_id, "down".to_string()))
+        }
+    }
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let spending_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 1, &[0u8; 32]);
+    let spending_txid = spending_tx.txid();
+
+    let mut archiving = LnResolver::new();
+    archiving.replace_active(&spending_tx.consensus_serialize());
+    archiving.replace_active(&genesis_tx.consensus_serialize());
+
+    let mut mining = LnResolver::new();
+    mining.add_onchain_tx(&spending_tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+
+    let resolver = ChainResolver::new()
+        .with_resolver(LocalResolver::new())
+        .with_resolver(&archiving)
+        .with_resolver(&mining);
+
+    // Unknown to the first backend, falls through to the second one.
+    let witness_id = XWitnessId::Bitcoin(spending_txid);
+    let tx = resolver.resolve_pub_witness(witness_id).unwrap();
+    assert_eq!(tx, XChain::Bitcoin(spending_tx));
+
+    // Archived by the second backend, but mined according to the third one.
+    let pos = WitnessPos::bitcoin(2.try_into().unwrap(), GENESIS_TIMESTAMP + 1).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Tentative);
+
+    let witness_id = XWitnessId::Bitcoin(Txid::coinbase());
+    assert!(matches!(
+        resolver.resolve_pub_witness_ord(witness_id),
+        Err(WitnessResolverError::Unknown(_))
+    ));
+
+    // A failing backend doesn't hide the answers of the others.
+    let resolver = ChainResolver::new()
+        .with_resolver(&mining)
+        .with_resolver(DownResolver);
+    let witness_id = XWitnessId::Bitcoin(spending_txid);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    assert!(matches!(
+        resolver.resolve_pub_witness_ord(witness_id),
+        Err(WitnessResolverError::Other(..))
+    ));
+}
+
+#[test]
+fn test_caching_resolver() {
+    use rgbstd::validation::ResolveWitness;
+    use rgbstd::vm::{WitnessOrd, WitnessPos};
+    use rgbstd::{XChain, XWitnessId};
+
+    use crate::resolvers::CachingResolver;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let spending_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 1, &[0u8; 32]);
+    let spending_txid = spending_tx.txid();
+
+    let mut inner = LnResolver::new();
+    inner.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    inner.add_onchain_tx(&spending_tx.consensus_serialize(), 5, GENESIS_TIMESTAMP + 1);
+
+    let cache_dir = tempfile::tempdir().unwrap();
+    {
+        let resolver = CachingResolver::with_cache_dir(inner, 6, cache_dir.path()).unwrap();
+
+        for txid in [genesis_txid, spending_txid] {
+            let witness_id = XWitnessId::Bitcoin(txid);
+            resolver.resolve_pub_witness(witness_id).unwrap();
+            resolver.resolve_pub_witness_ord(witness_id).unwrap();
+        }
+    }
+
+    // Corrupt entries, e.g. left by a power loss, are dropped on load.
+    let empty_tx = cache_dir.path().join("txs").join(Txid::coinbase().to_string());
+    let corrupt_pos = cache_dir.path().join("positions").join(Txid::coinbase().to_string());
+    std::fs::write(&empty_tx, b"").unwrap();
+    std::fs::write(&corrupt_pos, b"not a position").unwrap();
+
+    // Nothing left in the inner resolver, only the cache can answer.
+    let resolver = CachingResolver::with_cache_dir(LnResolver::new(), 6, cache_dir.path()).unwrap();
+    assert!(!empty_tx.exists());
+    assert!(!corrupt_pos.exists());
+
+    let witness_id = XWitnessId::Bitcoin(spending_txid);
+    let tx = resolver.resolve_pub_witness(witness_id).unwrap();
+    assert_eq!(tx, XChain::Bitcoin(spending_tx));
+    // Only 2 confirmations, not cached.
+    assert!(resolver.resolve_pub_witness_ord(witness_id).is_err());
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    let pos = WitnessPos::bitcoin(1.try_into().unwrap(), GENESIS_TIMESTAMP).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+}
+
+// Minimal HTTP server answering each request with `handler(path, body)`.
+fn serve_http(handler: impl Fn(&str, &str) -> (u16, Vec<u8>) + Send + 'static) -> String {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let path = request_line.split(' ').nth(1).unwrap().to_string();
+
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let (status, response) = handler(&path, &String::from_utf8(body).unwrap());
+            write!(
+                stream,
+                "HTTP/1.1 {status} OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                response.len(),
+            )
+            .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+    });
+
+    format!("http://{addr}")
+}
+
+#[test]
+fn test_bitcoind_resolver() {
+    use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+    use rgbstd::vm::{WitnessOrd, WitnessPos};
+    use rgbstd::{XChain, XWitnessId};
+
+    use crate::resolvers::BitcoindResolver;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let spending_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 1, &[0u8; 32]);
+    let spending_txid = spending_tx.txid();
+
+    let blockhash = "00".repeat(32);
+    let mined = serde_json::json!({
+        "hex": amplify::hex::ToHex::to_hex(genesis_tx.consensus_serialize().as_slice()),
+        "confirmations": 3,
+        "blockhash": blockhash,
+        "blocktime": GENESIS_TIMESTAMP,
+    });
+    let mempool = serde_json::json!({
+        "hex": amplify::hex::ToHex::to_hex(spending_tx.consensus_serialize().as_slice()),
+    });
+    // Mined in a block which has been reorged out since.
+    let stale_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 1)], 1, &[1u8; 32]);
+    let stale_txid = stale_tx.txid();
+    let stale = serde_json::json!({
+        "hex": amplify::hex::ToHex::to_hex(stale_tx.consensus_serialize().as_slice()),
+        "confirmations": 0,
+        "blockhash": "11".repeat(32),
+        "in_active_chain": false,
+    });
+    // Answered as if the credentials were wrong.
+    let unauthorized_txid = Txid::from([2u8; 32]);
+    let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let server_requests = requests.clone();
+    let regtest_genesis = bitcoin::constants::genesis_block(bitcoin::Network::Regtest).block_hash();
+    let url = serve_http(move |_, body| {
+        server_requests.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let request: serde_json::Value = serde_json::from_str(body).unwrap();
+        if request["method"] == "getblockhash" {
+            assert_eq!(request["params"][0], 0);
+            let hash = regtest_genesis.to_string();
+            let response = serde_json::json!({ "result": hash, "id": request["id"] });
+            return (200, response.to_string().into_bytes());
+        }
+        let param = request["params"][0].as_str().unwrap().to_string();
+        let result = match request["method"].as_str().unwrap() {
+            "getrawtransaction" if param == genesis_txid.to_string() => mined.clone(),
+            "getrawtransaction" if param == spending_txid.to_string() => mempool.clone(),
+            "getrawtransaction" if param == stale_txid.to_string() => stale.clone(),
+            "getrawtransaction" if param == unauthorized_txid.to_string() => {
+                return (401, vec![]);
+            }
+            "getblockheader" if param == blockhash => {
+                serde_json::json!({ "height": 7, "time": GENESIS_TIMESTAMP, "confirmations": 3 })
+            }
+            _ => {
+                let error = serde_json::json!({
+                    "result": null,
+                    "error": { "code": -5, "message": "No such mempool or blockchain transaction" },
+                    "id": request["id"],
+                });
+                return (500, error.to_string().into_bytes());
+            }
+        };
+        let response = serde_json::json!({ "result": result, "error": null, "id": request["id"] });
+        (200, response.to_string().into_bytes())
+    });
+
+    let resolver =
+        BitcoindResolver::with_auth(&url, "user", "password").with_network(Network::Regtest);
+
+    // Node is on regtest, not on mainnet.
+    let mainnet =
+        BitcoindResolver::with_auth(&url, "user", "password").with_network(Network::Mainnet);
+    assert!(matches!(
+        mainnet.resolve_pub_witness(XWitnessId::Bitcoin(genesis_txid)),
+        Err(WitnessResolverError::Other(..))
+    ));
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    let tx = resolver.resolve_pub_witness(witness_id).unwrap();
+    assert_eq!(tx, XChain::Bitcoin(genesis_tx));
+    let pos = WitnessPos::bitcoin(7.try_into().unwrap(), GENESIS_TIMESTAMP).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+
+    let witness_id = XWitnessId::Bitcoin(spending_txid);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Tentative);
+
+    let witness_id = XWitnessId::Bitcoin(stale_txid);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Archived);
+
+    let witness_id = XWitnessId::Bitcoin(Txid::coinbase());
+    assert!(matches!(
+        resolver.resolve_pub_witness(witness_id),
+        Err(WitnessResolverError::Unknown(_))
+    ));
+
+    // Authentication failures are not retried.
+    let before = requests.load(std::sync::atomic::Ordering::Relaxed);
+    let witness_id = XWitnessId::Bitcoin(unauthorized_txid);
+    assert!(matches!(
+        resolver.resolve_pub_witness(witness_id),
+        Err(WitnessResolverError::Other(..))
+    ));
+    assert_eq!(requests.load(std::sync::atomic::Ordering::Relaxed), before + 1);
+}
+
+// Minimal line-delimited JSON-RPC server, as spoken by Electrum servers.
+fn serve_electrum(
+    handler: impl Fn(&str, &serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+) -> String {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let handler = handler.clone();
+            std::thread::spawn(move || {
+                let reader = BufReader::new(stream.try_clone().unwrap());
+                for line in reader.lines() {
+                    let request: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                    let method = request["method"].as_str().unwrap();
+                    let response = match handler(method, &request["params"]) {
+                        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+                        Err(message) => serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "error": { "code": 2, "message": message },
+                        }),
+                    };
+                    writeln!(stream, "{response}").unwrap();
+                }
+            });
+        }
+    });
+
+    addr.to_string()
+}
+
+#[test]
+fn test_electrum_resolver() {
+    use amplify::ByteArray;
+    use bitcoin::hashes::{sha256d, Hash, HashEngine};
+    use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+    use rgbstd::vm::{WitnessOrd, WitnessPos};
+    use rgbstd::{XChain, XWitnessId};
+
+    use crate::resolvers::ElectrumResolver;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let spending_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 1, &[0u8; 32]);
+    let spending_txid = spending_tx.txid();
+
+    // The genesis tx is the second one of a two txs block.
+    let sibling = sha256d::Hash::hash(b"coinbase");
+    let merkle_root = {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(sibling.as_byte_array());
+        engine.input(&genesis_txid.to_byte_array());
+        sha256d::Hash::from_engine(engine)
+    };
+    let header = bitcoin::block::Header {
+        version: bitcoin::block::Version::TWO,
+        prev_blockhash: bitcoin::BlockHash::all_zeros(),
+        merkle_root: bitcoin::TxMerkleNode::from_raw_hash(merkle_root),
+        time: GENESIS_TIMESTAMP as u32 + 600,
+        bits: bitcoin::CompactTarget::from_consensus(0x207fffff),
+        nonce: 0,
+    };
+    let header = amplify::hex::ToHex::to_hex(bitcoin::consensus::serialize(&header).as_slice());
+    let regtest_genesis = bitcoin::constants::genesis_block(bitcoin::Network::Regtest).header;
+    let regtest_genesis =
+        amplify::hex::ToHex::to_hex(bitcoin::consensus::serialize(&regtest_genesis).as_slice());
+
+    // Known to the server, but missing from the history since it's double spent by `spending_tx`.
+    let double_spent_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 2, &[1u8; 32]);
+    let double_spent_txid = double_spent_tx.txid();
+
+    let txs = [
+        (genesis_txid, genesis_tx.clone(), Some(3)),
+        (spending_txid, spending_tx.clone(), Some(0)),
+        (double_spent_txid, double_spent_tx, None),
+    ];
+    let addr = serve_electrum(move |method, params| {
+        let to_hex = |tx: &BpTx| amplify::hex::ToHex::to_hex(tx.consensus_serialize().as_slice());
+        match method {
+            "blockchain.transaction.get" => txs
+                .iter()
+                .find(|(txid, _, _)| params[0] == txid.to_string())
+                .map(|(_, tx, _)| serde_json::json!(to_hex(tx)))
+                .ok_or_else(|| {
+                    "daemon error: DaemonError({'code': -5, 'message': 'unknown tx'})".to_string()
+                }),
+            "blockchain.scripthash.get_history" => Ok(serde_json::json!(txs
+                .iter()
+                .filter_map(|(txid, _, height)| {
+                    Some(serde_json::json!({ "tx_hash": txid.to_string(), "height": (*height)? }))
+                })
+                .collect::<Vec<_>>())),
+            "blockchain.transaction.get_merkle" => Ok(serde_json::json!({
+                "block_height": 3,
+                "merkle": [bitcoin::TxMerkleNode::from_raw_hash(sibling).to_string()],
+                "pos": 1,
+            })),
+            "blockchain.block.header" if params[0] == 0 => Ok(serde_json::json!(regtest_genesis)),
+            "blockchain.block.header" => Ok(serde_json::json!(header)),
+            _ => Err(format!("unknown method {method}")),
+        }
+    });
+
+    let resolver = ElectrumResolver::new(&addr).with_network(Network::Regtest);
+
+    // Server is on regtest, not on signet.
+    let signet = ElectrumResolver::new(&addr).with_network(Network::Signet);
+    assert!(matches!(
+        signet.resolve_pub_witness(XWitnessId::Bitcoin(genesis_txid)),
+        Err(WitnessResolverError::Other(..))
+    ));
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    let tx = resolver.resolve_pub_witness(witness_id).unwrap();
+    assert_eq!(tx, XChain::Bitcoin(genesis_tx));
+    let pos = WitnessPos::bitcoin(3.try_into().unwrap(), GENESIS_TIMESTAMP + 600).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+
+    let witness_id = XWitnessId::Bitcoin(spending_txid);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Tentative);
+
+    let witness_id = XWitnessId::Bitcoin(double_spent_txid);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Archived);
+
+    let witness_id = XWitnessId::Bitcoin(Txid::coinbase());
+    assert!(matches!(
+        resolver.resolve_pub_witness(witness_id),
+        Err(WitnessResolverError::Unknown(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_async_online_resolver() {
+    use rgbstd::vm::{WitnessOrd, WitnessPos};
+    use rgbstd::validation::ResolveWitness;
+    use rgbstd::XWitnessId;
+
+    use crate::resolvers::AsyncOnlineResolver;
+
+    let network = Network::Regtest;
+    let genesis_tx = get_first_tx();
+    let contract = issue_test_contract(&genesis_tx, 8, network);
+    let (_, valid_transfer) = basic_transfer(genesis_tx, contract, network);
+    let transfer = valid_transfer.into_consignment();
+
+    let witness_txes = transfer
+        .bundles
+        .iter()
+        .filter_map(|bw| bw.pub_witness.as_reduced_unsafe().tx().cloned())
+        .collect::<Vec<_>>();
+    assert!(!witness_txes.is_empty());
+
+    let txes = witness_txes.clone();
+    let regtest_genesis = bitcoin::constants::genesis_block(bitcoin::Network::Regtest).block_hash();
+    let url = serve_http(move |path, _| {
+        if path == "/block-height/0" {
+            return (200, regtest_genesis.to_string().into_bytes());
+        }
+        let mut segments = path.trim_start_matches('/').split('/');
+        let (Some("tx"), Some(txid), Some(endpoint)) =
+            (segments.next(), segments.next(), segments.next())
+        else {
+            return (404, vec![]);
+        };
+        let Some(tx) = txes.iter().find(|tx| tx.txid().to_string() == txid) else {
+            return (404, vec![]);
+        };
+        match endpoint {
+            "raw" => (200, tx.consensus_serialize()),
+            "status" => {
+                let status = serde_json::json!({
+                    "confirmed": true,
+                    "block_height": 2,
+                    "block_hash": "00".repeat(32),
+                    "block_time": GENESIS_TIMESTAMP + 1,
+                });
+                (200, status.to_string().into_bytes())
+            }
+            _ => (404, vec![]),
+        }
+    });
+
+    // Server is on regtest, not on testnet4.
+    let testnet4 = AsyncOnlineResolver::new(&url).unwrap().with_network(Network::Testnet4);
+    assert!(testnet4.prefetch(&transfer).await.is_err());
+
+    let resolver = AsyncOnlineResolver::new(&url).unwrap().with_network(network);
+    let prefetched = resolver.prefetch(&transfer).await.unwrap();
+
+    let pos = WitnessPos::bitcoin(2.try_into().unwrap(), GENESIS_TIMESTAMP + 1).unwrap();
+    for tx in witness_txes {
+        let witness_id = XWitnessId::Bitcoin(tx.txid());
+        let ord = prefetched.resolve_pub_witness_ord(witness_id).unwrap();
+        assert_eq!(ord, WitnessOrd::Mined(pos));
+    }
+
+    transfer.validate(&prefetched, network.is_testnet()).unwrap();
+}
+
+#[test]
+fn test_online_resolver_retry() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+    use rgbstd::{XChain, XWitnessId};
+
+    use crate::resolvers::OnlineResolver;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let url = serve_http(move |path, _| {
+        // Fails the first attempt of each request.
+        if counter.fetch_add(1, Ordering::SeqCst).is_multiple_of(2) {
+            return (503, vec![]);
+        }
+        if path == format!("/tx/{genesis_txid}/raw") {
+            (200, genesis_tx.consensus_serialize())
+        } else {
+            (404, b"Transaction not found".to_vec())
+        }
+    });
+
+    let resolver = OnlineResolver::builder(&url)
+        .max_retries(3)
+        .min_delay(Duration::from_millis(1))
+        .max_delay(Duration::from_millis(10))
+        .timeout(Duration::from_secs(5))
+        .header("Authorization", "Bearer token")
+        .build();
+    assert_eq!(resolver.esplora_url(), url);
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    let tx = resolver.resolve_pub_witness(witness_id).unwrap();
+    assert_eq!(tx, XChain::Bitcoin(get_first_tx()));
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    // The not found answer is definitive, so only the failed attempt is retried.
+    let witness_id = XWitnessId::Bitcoin(Txid::coinbase());
+    assert!(matches!(
+        resolver.resolve_pub_witness(witness_id),
+        Err(WitnessResolverError::Unknown(_))
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_online_resolver_unknown_witness() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+    use rgbstd::vm::WitnessOrd;
+    use rgbstd::XWitnessId;
+
+    use crate::resolvers::OnlineResolver;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let spending_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 1, &[0u8; 32]);
+    let conflicting_tx = build_rgb_tx(&[Outpoint::new(genesis_txid, 0)], 1, &[1u8; 32]);
+    let conflicting_txid = conflicting_tx.txid();
+
+    let double_spent = Arc::new(AtomicBool::new(false));
+    let flag = double_spent.clone();
+    let regtest_genesis = bitcoin::constants::genesis_block(bitcoin::Network::Regtest).block_hash();
+    let url = serve_http(move |path, _| {
+        if path == "/block-height/0" {
+            (200, regtest_genesis.to_string().into_bytes())
+        } else if path == format!("/tx/{genesis_txid}/outspend/0") {
+            let status = if flag.load(Ordering::SeqCst) {
+                serde_json::json!({ "spent": true, "txid": conflicting_txid.to_string(), "vin": 0 })
+            } else {
+                serde_json::json!({ "spent": false })
+            };
+            (200, status.to_string().into_bytes())
+        } else {
+            (404, b"Transaction not found".to_vec())
+        }
+    });
+
+    let mut resolver = OnlineResolver::builder(&url).network(Network::Regtest).build();
+
+    // Server is on regtest, not on testnet4.
+    let testnet4 = OnlineResolver::builder(&url).network(Network::Testnet4).build();
+    let witness_id = XWitnessId::Bitcoin(spending_tx.txid());
+    assert!(matches!(
+        testnet4.resolve_pub_witness_ord(witness_id),
+        Err(WitnessResolverError::Other(..))
+    ));
+
+    // Neither the server nor the resolver know about it.
+    assert!(matches!(
+        resolver.resolve_pub_witness_ord(witness_id),
+        Err(WitnessResolverError::Unknown(_))
+    ));
+
+    assert!(resolver.add_witness_tx(&[0u8; 4]).is_err());
+
+    // Not broadcast yet.
+    resolver.add_witness_tx(&spending_tx.consensus_serialize()).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Tentative);
+
+    // Replaced by a conflicting tx.
+    double_spent.store(true, Ordering::SeqCst);
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Archived);
+}
+
+// Regtest header with a valid proof of work.
+fn mine_header(
+    prev_blockhash: bitcoin::BlockHash,
+    merkle_root: bitcoin::TxMerkleNode,
+    time: u32,
+) -> bitcoin::block::Header {
+    let mut header = bitcoin::block::Header {
+        version: bitcoin::block::Version::TWO,
+        prev_blockhash,
+        merkle_root,
+        time,
+        bits: bitcoin::CompactTarget::from_consensus(0x207fffff),
+        nonce: 0,
+    };
+    while header.validate_pow(header.target()).is_err() {
+        header.nonce += 1;
+    }
+    header
+}
+
+#[test]
+fn test_header_chain() {
+    use bitcoin::hashes::Hash;
+
+    use crate::resolvers::{HeaderChain, SpvError};
+
+    let time = GENESIS_TIMESTAMP as u32;
+    let merkle_root = bitcoin::TxMerkleNode::all_zeros();
+    let checkpoint = mine_header(bitcoin::BlockHash::all_zeros(), merkle_root, time);
+    assert_eq!(
+        HeaderChain::new(Network::Liquid, 100, checkpoint).unwrap_err(),
+        crate::error::NetworkError::Unsupported(Network::Liquid)
+    );
+    let mut chain = HeaderChain::new(Network::Regtest, 100, checkpoint).unwrap();
+
+    let header = mine_header(checkpoint.block_hash(), merkle_root, time + 1);
+    chain.push(header).unwrap();
+    assert_eq!(chain.tip_height(), 101);
+    assert_eq!(chain.tip_hash(), header.block_hash());
+
+    let disconnected = mine_header(checkpoint.block_hash(), merkle_root, time + 2);
+    assert_eq!(chain.push(disconnected), Err(SpvError::Disconnected { height: 102 }));
+
+    let mut invalid_pow = mine_header(header.block_hash(), merkle_root, time + 2);
+    invalid_pow.bits = bitcoin::CompactTarget::from_consensus(0x1d00ffff);
+    assert_eq!(chain.push(invalid_pow), Err(SpvError::InvalidProofOfWork { height: 102 }));
+
+    chain.rewind(100);
+    assert_eq!(chain.tip_height(), 100);
+    chain.push(mine_header(checkpoint.block_hash(), merkle_root, time + 3)).unwrap();
+}
+
+#[test]
+fn test_online_resolver_spv() {
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Arc;
+
+    use amplify::ByteArray;
+    use bitcoin::hashes::Hash;
+    use rgbstd::validation::ResolveWitness;
+    use rgbstd::vm::{WitnessOrd, WitnessPos};
+    use rgbstd::XWitnessId;
+
+    use crate::resolvers::{HeaderChain, OnlineResolver};
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+
+    // The genesis tx is the only one of the block at height 2.
+    let time = GENESIS_TIMESTAMP as u32;
+    let merkle_root = bitcoin::TxMerkleNode::all_zeros();
+    let checkpoint = mine_header(bitcoin::BlockHash::all_zeros(), merkle_root, time);
+    let merkle_root = bitcoin::TxMerkleNode::from_byte_array(genesis_txid.to_byte_array());
+    let block_2 = mine_header(checkpoint.block_hash(), merkle_root, time + 600);
+    let block_3 = mine_header(block_2.block_hash(), bitcoin::TxMerkleNode::all_zeros(), time + 1200);
+    // After a reorg, the genesis tx is mined at height 3 instead.
+    let zeros = bitcoin::TxMerkleNode::all_zeros();
+    let reorged_2 = mine_header(checkpoint.block_hash(), zeros, time + 601);
+    let reorged_3 = mine_header(reorged_2.block_hash(), merkle_root, time + 1201);
+    let reorged_4 = mine_header(reorged_3.block_hash(), zeros, time + 1801);
+
+    // 0: honest, 1: lying about the block of the witness, 2: reorged,
+    // 3: lagging below the checkpoint while claiming the witness above our tip.
+    let stage = Arc::new(AtomicU8::new(0));
+    let server_stage = stage.clone();
+    let url = serve_http(move |path, _| {
+        let json = |value: serde_json::Value| (200, value.to_string().into_bytes());
+        let stage = server_stage.load(Ordering::SeqCst);
+        let chain = match stage {
+            0 | 1 => vec![checkpoint, block_2, block_3],
+            2 => vec![checkpoint, reorged_2, reorged_3, reorged_4],
+            _ => vec![],
+        };
+        let height = match stage {
+            0 => 2,
+            1 | 2 => 3,
+            _ => 5,
+        };
+        let header_path = |header: &bitcoin::block::Header| {
+            format!("/block/{}/header", header.block_hash())
+        };
+        if path == "/blocks/tip/height" {
+            return (200, chain.len().to_string().into_bytes());
+        }
+        if let Some(height) = path.strip_prefix("/block-height/") {
+            let height = height.parse::<usize>().unwrap();
+            // Block below the checkpoint, only asked for when the server lags behind it.
+            if height == 0 {
+                return (200, "00".repeat(32).into_bytes());
+            }
+            return match chain.get(height - 1) {
+                Some(header) => (200, header.block_hash().to_string().into_bytes()),
+                None => (404, vec![]),
+            };
+        }
+        if let Some(header) = chain.iter().find(|header| path == header_path(header)) {
+            let header = bitcoin::consensus::serialize(header);
+            return (200, amplify::hex::ToHex::to_hex(header.as_slice()).into_bytes());
+        }
+        match path {
+            _ if path == format!("/tx/{genesis_txid}/raw") => (200, genesis_tx.consensus_serialize()),
+            _ if path == format!("/tx/{genesis_txid}/status") => json(serde_json::json!({
+                "confirmed": true,
+                "block_height": height,
+                "block_hash": "00".repeat(32),
+                // Not trusted in SPV mode, the header timestamp is used instead.
+                "block_time": GENESIS_TIMESTAMP + 1,
+            })),
+            _ if path == format!("/tx/{genesis_txid}/merkle-proof") => json(serde_json::json!({
+                "block_height": height,
+                "merkle": [],
+                "pos": 0,
+            })),
+            _ => (404, vec![]),
+        }
+    });
+
+    let header_chain = HeaderChain::new(Network::Regtest, 1, checkpoint).unwrap();
+    let resolver = OnlineResolver::builder(&url).spv(header_chain).build();
+
+    let witness_id = XWitnessId::Bitcoin(genesis_txid);
+    let pos = WitnessPos::bitcoin(2.try_into().unwrap(), GENESIS_TIMESTAMP + 600).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+    assert_eq!(resolver.header_chain().unwrap().tip_height(), 3);
+
+    // The witness is not in the block the server claims.
+    stage.store(1, Ordering::SeqCst);
+    assert!(resolver.resolve_pub_witness_ord(witness_id).is_err());
+
+    // Our headers from height 2 are reorged out, they are synced again from the fork point.
+    stage.store(2, Ordering::SeqCst);
+    let pos = WitnessPos::bitcoin(3.try_into().unwrap(), GENESIS_TIMESTAMP + 1201).unwrap();
+    let ord = resolver.resolve_pub_witness_ord(witness_id).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+    let header_chain = resolver.header_chain().unwrap();
+    assert_eq!(header_chain.tip_height(), 4);
+    assert_eq!(header_chain.tip_hash(), reorged_4.block_hash());
+
+    // A server behind our checkpoint is refused, not trusted to rewind past it.
+    stage.store(3, Ordering::SeqCst);
+    assert!(resolver.resolve_pub_witness_ord(witness_id).is_err());
+    assert_eq!(resolver.header_chain().unwrap().tip_height(), 4);
+}
+
+#[test]
+fn test_local_resolver_ordering() {
+    use bitcoin::hashes::Hash;
+    use rgbstd::containers::ToWitnessId;
+    use rgbstd::validation::ResolveWitness;
+    use rgbstd::vm::{WitnessOrd, WitnessPos};
+    use rgbstd::XWitnessId;
+
+    use crate::error::BlockError;
+    use crate::resolvers::LocalResolver;
+
+    let network = Network::Regtest;
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let contract = issue_test_contract(&genesis_tx, 8, network);
+    let (_, valid_transfer) = basic_transfer(genesis_tx.clone(), contract, network);
+    let transfer = valid_transfer.into_consignment();
+
+    let mut resolver = LocalResolver::with_consignment(&transfer);
+    let witness_ids = transfer
+        .bundles
+        .iter()
+        .map(|bw| *bw.pub_witness.to_witness_id().as_reduced_unsafe())
+        .collect::<Vec<_>>();
+    for &txid in &witness_ids {
+        let ord = resolver.resolve_pub_witness_ord(XWitnessId::Bitcoin(txid)).unwrap();
+        assert_eq!(ord, WitnessOrd::Tentative);
+    }
+
+    let time = GENESIS_TIMESTAMP as u32;
+    let merkle_root = bitcoin::TxMerkleNode::all_zeros();
+    let block = bitcoin::Block {
+        header: mine_header(bitcoin::BlockHash::all_zeros(), merkle_root, time),
+        txdata: vec![bitcoin::consensus::deserialize(&genesis_tx.consensus_serialize()).unwrap()],
+    };
+    resolver.add_block(1, &bitcoin::consensus::serialize(&block)).unwrap();
+    let header = mine_header(block.block_hash(), merkle_root, time + 600);
+    let header = bitcoin::consensus::serialize(&header);
+    resolver.add_block_header(2, &header, witness_ids.clone()).unwrap();
+
+    assert!(matches!(resolver.add_block(3, &[0u8; 8]), Err(BlockError::InvalidData(_))));
+    let no_txids: [Txid; 0] = [];
+    assert_eq!(resolver.add_block_header(0, &header, no_txids), Err(BlockError::InvalidHeight));
+    let early = bitcoin::consensus::serialize(&mine_header(block.block_hash(), merkle_root, 1));
+    assert_eq!(
+        resolver.add_block_header(3, &early, no_txids),
+        Err(BlockError::InvalidTimestamp(1))
+    );
+
+    let ord = resolver.resolve_pub_witness_ord(XWitnessId::Bitcoin(genesis_txid)).unwrap();
+    let pos = WitnessPos::bitcoin(1.try_into().unwrap(), GENESIS_TIMESTAMP).unwrap();
+    assert_eq!(ord, WitnessOrd::Mined(pos));
+    let pos = WitnessPos::bitcoin(2.try_into().unwrap(), GENESIS_TIMESTAMP + 600).unwrap();
+    for txid in witness_ids {
+        let ord = resolver.resolve_pub_witness_ord(XWitnessId::Bitcoin(txid)).unwrap();
+        assert_eq!(ord, WitnessOrd::Mined(pos));
+    }
+
+    transfer.validate(&resolver, network.is_testnet()).unwrap();
+}
+
+#[test]
+fn test_fascia_resolver() {
+    use rgbstd::containers::ToWitnessId;
+    use rgbstd::validation::{ResolveWitness, WitnessResolverError};
+    use rgbstd::vm::WitnessOrd;
+    use rgbstd::{XChain, XWitnessId};
+
+    use crate::resolvers::FasciaResolver;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, _, mut stock) = get_test_contract(network);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 100);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(&stock, prev_outputs, rgb_assignments, None, network).unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let spending_tx = build_rgb_tx(&available_utxos, 1, &commitment);
+    let spending_txid = spending_tx.txid();
+
+    let fascia = partial_fascia.clone().complete_with_txid(spending_txid);
+    let resolver = FasciaResolver::new(&fascia);
+    let witness_id = fascia.witness.to_witness_id();
+    assert!(matches!(
+        resolver.resolve_pub_witness(witness_id),
+        Err(WitnessResolverError::Other(..))
+    ));
+
+    let fascia = partial_fascia.complete_with_tx(&spending_tx.consensus_serialize());
+    let resolver = FasciaResolver::new(&fascia);
+    assert_eq!(resolver.resolve_pub_witness(witness_id).unwrap(), XChain::Bitcoin(spending_tx));
+    assert_eq!(resolver.resolve_pub_witness_ord(witness_id).unwrap(), WitnessOrd::Tentative);
+    let other_id = XWitnessId::Bitcoin(genesis_txid);
+    assert!(matches!(
+        resolver.resolve_pub_witness(other_id),
+        Err(WitnessResolverError::Unknown(_))
+    ));
+
+    stock.consume_fascia(fascia, &resolver).unwrap();
+    let balance = rgb_balance(
+        &stock,
+        contract_id,
+        &[Outpoint::new(spending_txid, 0)],
+        network,
+    )
+    .unwrap();
+    assert_eq!(balance, 100);
+}
+
+#[test]
+fn test_update_witnesses() {
+    use crate::api::rgb_update_witnesses;
+    use crate::types::BalanceChange;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, mut resolver, mut stock) = get_test_contract(network);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 100);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(&stock, prev_outputs, rgb_assignments, None, network).unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let spending_tx = build_rgb_tx(&available_utxos, 1, &commitment);
+    let spending_txid = spending_tx.txid();
+    let fascia = partial_fascia.complete_with_tx(&spending_tx.consensus_serialize());
+
+    resolver.add_onchain_tx(&spending_tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock.consume_fascia(fascia, &resolver).unwrap();
+    let output = Outpoint::new(spending_txid, 0);
+    assert_eq!(rgb_balance(&stock, contract_id, &[output], network).unwrap(), 100);
+
+    // Nothing changed.
+    let report = rgb_update_witnesses(&mut stock, &resolver).unwrap();
+    assert!(report.balance_changes.is_empty());
+    assert!(report.failed.is_empty());
+
+    // The witness is reorged out and replaced by another tx.
+    let mut replaced = LnResolver::new();
+    replaced.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    replaced.replace_active(&spending_tx.consensus_serialize());
+    let replacement_tx = build_rgb_tx(&available_utxos, 2, &[0; 32]);
+    replaced.replace_active(&replacement_tx.consensus_serialize());
+
+    let report = rgb_update_witnesses(&mut stock, &replaced).unwrap();
+    let change = BalanceChange {
+        contract_id,
+        outpoint: output,
+        before: 100,
+        after: 0,
+    };
+    assert_eq!(report.balance_changes, [change]);
+    assert_eq!(rgb_balance(&stock, contract_id, &[output], network).unwrap(), 0);
+
+    // Unknown witnesses keep their ordering.
+    let report = rgb_update_witnesses(&mut stock, LnResolver::new()).unwrap();
+    assert!(report.balance_changes.is_empty());
+    assert_eq!(report.failed.keys().collect::<Vec<_>>(), [&spending_txid.into()]);
+
+    let report = rgb_update_witnesses(&mut stock, &resolver).unwrap();
+    let change = BalanceChange {
+        before: 0,
+        after: 100,
+        ..change
+    };
+    assert_eq!(report.balance_changes, [change]);
+}
+
+#[test]
+fn test_rbf_recolor() {
+    use crate::api::rgb_rbf_recolor;
+    use crate::error::RbfError;
+    use crate::resolvers::FasciaResolver;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, mut resolver, mut stock) = get_test_contract(network);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 100);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(&stock, prev_outputs, rgb_assignments, None, network).unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let replaced_tx = build_rgb_tx(&available_utxos, 1, &commitment);
+    let fascia = partial_fascia
+        .clone()
+        .complete_with_tx(&replaced_tx.consensus_serialize());
+    let fascia_resolver = FasciaResolver::new(&fascia);
+    stock.consume_fascia(fascia, fascia_resolver).unwrap();
+
+    let mut replacement_tx = replaced_tx.clone();
+    replacement_tx.lock_time = LockTime::from_height(1).unwrap();
+
+    let recolor = |stock: &mut Stock, replaced_tx: &BpTx, replacement_tx: &BpTx| {
+        rgb_rbf_recolor(
+            stock,
+            partial_fascia.clone(),
+            &replaced_tx.consensus_serialize(),
+            &replacement_tx.consensus_serialize(),
+        )
+    };
+    assert_eq!(
+        recolor(&mut stock, &replaced_tx, &replaced_tx),
+        Err(RbfError::NotReplaced)
+    );
+    let other_tx = build_rgb_tx(&available_utxos, 1, &[0; 32]);
+    assert_eq!(
+        recolor(&mut stock, &other_tx, &replacement_tx),
+        Err(RbfError::FasciaMismatch)
+    );
+    let moved_commitment = build_rgb_tx(&available_utxos, 2, &commitment);
+    assert_eq!(
+        recolor(&mut stock, &replaced_tx, &moved_commitment),
+        Err(RbfError::CommitmentChanged)
+    );
+    let other_inputs = [Outpoint::new(genesis_txid, 0), Outpoint::new(genesis_txid, 1)];
+    let other_inputs_tx = build_rgb_tx(&other_inputs, 1, &commitment);
+    assert_eq!(
+        recolor(&mut stock, &replaced_tx, &other_inputs_tx),
+        Err(RbfError::InputsChanged)
+    );
+    let invalid_tx = rgb_rbf_recolor(
+        &mut stock,
+        partial_fascia.clone(),
+        &[0; 3],
+        &replacement_tx.consensus_serialize(),
+    );
+    assert!(matches!(invalid_tx, Err(RbfError::Decode(_))));
+
+    recolor(&mut stock, &replaced_tx, &replacement_tx).unwrap();
+
+    let replaced_output = Outpoint::new(replaced_tx.txid(), 0);
+    let replacement_output = Outpoint::new(replacement_tx.txid(), 0);
+    assert_eq!(rgb_balance(&stock, contract_id, &[replaced_output], network).unwrap(), 0);
+    assert_eq!(rgb_balance(&stock, contract_id, &[replacement_output], network).unwrap(), 100);
+
+    resolver.add_onchain_tx(&replacement_tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    let transfer = rgb_transfer(&stock, contract_id, &[replacement_output], None, network).unwrap();
+    transfer.validate(&resolver, network.is_testnet()).unwrap();
+}
+
+#[test]
+fn test_persistent_stock() {
+    use crate::api::{create_stock, open_stock};
+
+    let network = Network::Regtest;
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let contract = issue_test_contract(&genesis_tx, 8, network);
+    let contract_id: ContractId = contract.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+
+    let dir = tempfile::tempdir().unwrap();
+    let stock_path = dir.path().join("stock");
+    assert_eq!(open_stock(&stock_path).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+    let mut stock = create_stock(&stock_path).unwrap();
+    stock.import_contract(contract, &resolver).unwrap();
+    assert_eq!(
+        open_stock(&stock_path).unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+    drop(stock);
+
+    assert_eq!(
+        create_stock(&stock_path).unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+    let stock = open_stock(&stock_path).unwrap();
+    let outputs = [Outpoint::new(genesis_txid, 0)];
+    assert_eq!(rgb_balance(&stock, contract_id, &outputs, network).unwrap(), 100);
+
+    let leftovers = std::fs::read_dir(&stock_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn test_sqlite_stock() {
+    use rgbstd::persistence::{StashReadProvider, StateReadProvider};
+
+    use crate::api::{create_sqlite_stock, open_sqlite_stock};
+    use crate::resolvers::FasciaResolver;
+
+    let network = Network::Regtest;
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let contract = issue_test_contract(&genesis_tx, 8, network);
+    let contract_id: ContractId = contract.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+
+    let dir = tempfile::tempdir().unwrap();
+    let stock_path = dir.path().join("stock.db");
+    assert_eq!(
+        open_sqlite_stock(&stock_path).unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    );
+
+    let mut stock = create_sqlite_stock(&stock_path).unwrap();
+    stock.import_contract(contract.clone(), &resolver).unwrap();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 30);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        network,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let tx = build_rgb_tx(&available_utxos, 2, &commitment);
+    let spending_txid = tx.txid();
+    let fascia = partial_fascia.complete_with_tx(&tx.consensus_serialize());
+    resolver.add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock.consume_fascia(fascia.clone(), &resolver).unwrap();
+    drop(stock);
+
+    assert_eq!(
+        create_sqlite_stock(&stock_path).unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+    let stock = open_sqlite_stock(&stock_path).unwrap();
+    assert_eq!(
+        rgb_balance(&stock, contract_id, &[Outpoint::new(spending_txid, 0)], network).unwrap(),
+        30
+    );
+    assert_eq!(
+        rgb_balance(&stock, contract_id, &[Outpoint::new(spending_txid, 1)], network).unwrap(),
+        70
+    );
+    let transfer = rgb_transfer(
+        &stock,
+        contract_id,
+        &[Outpoint::new(spending_txid, 0)],
+        None,
+        network,
+    )
+    .unwrap();
+    let transfer = transfer.validate(&resolver, network.is_testnet()).unwrap();
+
+    // Witness ords are updated in place.
+    let mut replaced = LnResolver::new();
+    replaced.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    replaced.replace_active(&tx.consensus_serialize());
+    replaced.replace_active(&build_rgb_tx(&available_utxos, 2, &[0; 32]).consensus_serialize());
+    let mut stock = stock;
+    stock.update_witnesses(&replaced, 0).unwrap();
+    assert_eq!(
+        rgb_balance(&stock, contract_id, &[Outpoint::new(spending_txid, 0)], network).unwrap(),
+        0
+    );
+    stock.update_witnesses(&resolver, 0).unwrap();
+    assert_eq!(
+        rgb_balance(&stock, contract_id, &[Outpoint::new(spending_txid, 0)], network).unwrap(),
+        30
+    );
+    drop(stock);
+
+    // The witness of the transfer can't be resolved, so it's rejected after
+    // the contract has already been registered in the state.
+    let other_path = dir.path().join("other.db");
+    let mut stock = create_sqlite_stock(&other_path).unwrap();
+    stock
+        .accept_transfer(transfer.clone(), LnResolver::new())
+        .unwrap_err();
+    assert!(stock.as_state_provider().contract_state(contract_id.to_raw()).is_err());
+
+    // The contract is unknown to this stock, so the fascia is rejected after
+    // its witness has already been stored. None of it must be kept, neither
+    // in memory nor in the database.
+    stock
+        .consume_fascia(fascia.clone(), FasciaResolver::new(&fascia))
+        .unwrap_err();
+    assert_eq!(stock.as_stash_provider().witness_ids().unwrap().count(), 0);
+
+    stock.accept_transfer(transfer, &resolver).unwrap();
+    assert_eq!(
+        rgb_balance(&stock, contract_id, &[Outpoint::new(spending_txid, 0)], network).unwrap(),
+        30
+    );
+    drop(stock);
+    let stock = open_sqlite_stock(&other_path).unwrap();
+    assert_eq!(stock.as_stash_provider().witness_ids().unwrap().count(), 1);
+    assert_eq!(
+        rgb_balance(&stock, contract_id, &[Outpoint::new(spending_txid, 0)], network).unwrap(),
+        30
+    );
+}
+
+#[test]
+fn test_backup_restore() {
+    use rgbstd::persistence::StashReadProvider;
+
+    use crate::api::{
+        create_sqlite_stock, open_sqlite_stock, rgb_backup, rgb_build_invoice, rgb_restore,
+    };
+    use crate::error::BackupError;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, mut resolver, mut stock) = get_test_contract(network);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 30);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        network,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let tx = build_rgb_tx(&available_utxos, 2, &commitment);
+    let spending_txid = tx.txid();
+    resolver.add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock
+        .consume_fascia(partial_fascia.complete_with_tx(&tx.consensus_serialize()), &resolver)
+        .unwrap();
+
+    rgb_build_invoice(
+        &mut stock,
+        contract_id,
+        10,
+        Beneficiary::WitnessVout(0),
+        [],
+        None,
+        Network::Regtest,
+    )
+    .unwrap();
+    let seals = stock
+        .as_stash_provider()
+        .secret_seals()
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(seals.len(), 1);
+
+    let outputs = [Outpoint::new(spending_txid, 0), Outpoint::new(spending_txid, 1)];
+    for passphrase in [None, Some("secret")] {
+        let mut archive = vec![];
+        rgb_backup(&stock, &mut archive, passphrase).unwrap();
+
+        let mut restored = Stock::in_memory();
+        rgb_restore(&mut restored, archive.as_slice(), passphrase, None).unwrap();
+        assert_eq!(rgb_balance(&restored, contract_id, &outputs[..1], network).unwrap(), 30);
+        assert_eq!(rgb_balance(&restored, contract_id, &outputs[1..], network).unwrap(), 70);
+        let restored_seals = restored
+            .as_stash_provider()
+            .secret_seals()
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(restored_seals, seals);
+
+        let transfer = rgb_transfer(&restored, contract_id, &outputs[..1], None, network).unwrap();
+        transfer.validate(&resolver, network.is_testnet()).unwrap();
+
+        let mut corrupted = archive.clone();
+        corrupted[20] ^= 1;
+        assert!(matches!(
+            rgb_restore(&mut Stock::in_memory(), corrupted.as_slice(), passphrase, None),
+            Err(BackupError::ChecksumMismatch)
+        ));
+    }
+
+    let mut archive = vec![];
+    rgb_backup(&stock, &mut archive, Some("secret")).unwrap();
+    assert!(matches!(
+        rgb_restore(&mut Stock::in_memory(), archive.as_slice(), None, None),
+        Err(BackupError::PassphraseRequired)
+    ));
+    assert!(matches!(
+        rgb_restore(&mut Stock::in_memory(), archive.as_slice(), Some("wrong"), None),
+        Err(BackupError::WrongPassphrase)
+    ));
+    assert!(matches!(
+        rgb_restore(&mut Stock::in_memory(), &b"not a backup"[..], None, None),
+        Err(BackupError::NotBackup)
+    ));
+
+    // Restored into a persistent stock.
+    let dir = tempfile::tempdir().unwrap();
+    let stock_path = dir.path().join("stock.db");
+    let mut restored = create_sqlite_stock(&stock_path).unwrap();
+    rgb_restore(&mut restored, archive.as_slice(), Some("secret"), None).unwrap();
+    drop(restored);
+    let restored = open_sqlite_stock(&stock_path).unwrap();
+    assert_eq!(rgb_balance(&restored, contract_id, &outputs[..1], network).unwrap(), 30);
+}
+
+#[test]
+fn test_backup_restore_txid_witness() {
+    use crate::api::{rgb_backup, rgb_restore};
+    use crate::error::BackupError;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, mut resolver, mut stock) = get_test_contract(network);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 30);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        network,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let tx = build_rgb_tx(&available_utxos, 2, &commitment);
+    let spending_txid = tx.txid();
+    resolver.add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    // The witness is stored without its tx, so it's not in the backup.
+    stock
+        .consume_fascia(partial_fascia.complete_with_txid(spending_txid), &resolver)
+        .unwrap();
+
+    let mut archive = vec![];
+    rgb_backup(&stock, &mut archive, None).unwrap();
+    assert!(matches!(
+        rgb_restore(&mut Stock::in_memory(), archive.as_slice(), None, None),
+        Err(BackupError::InvalidContract(..))
+    ));
+
+    let mut restored = Stock::in_memory();
+    rgb_restore(&mut restored, archive.as_slice(), None, Some(&resolver)).unwrap();
+    let payment = [Outpoint::new(spending_txid, 0)];
+    assert_eq!(rgb_balance(&restored, contract_id, &payment, network).unwrap(), 30);
+}
+
+#[test]
+fn test_rgb_wallet() {
+    use crate::api::{rgb_build_invoice, rgb_validate_transfer};
+    use crate::error::WalletError;
+    use crate::wallet::RgbWallet;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, resolver, stock) = get_test_contract(network);
+    let genesis_txid = genesis_tx.txid();
+
+    let genesis_outpoint = Outpoint::new(genesis_txid, 0);
+    let mut receiver_resolver = LnResolver::new();
+    receiver_resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut sender = RgbWallet::with_utxos(stock, resolver, network, [genesis_outpoint]);
+    let mut receiver = RgbWallet::new(get_stock(), receiver_resolver, network);
+    assert_eq!(sender.balance(contract_id), 100);
+    assert_eq!(receiver.balance(contract_id), 0);
+
+    let invoice = rgb_build_invoice(
+        receiver.stock_mut(),
+        contract_id,
+        30,
+        Beneficiary::WitnessVout(0),
+        [],
+        None,
+        network,
+    )
+    .unwrap();
+    let secret_seal = match invoice.beneficiary.into_inner() {
+        rgbinvoice::Beneficiary::BlindedSeal(seal) => seal.to_byte_array(),
+        _ => unreachable!(),
+    };
+
+    let mut too_much = RgbAssignments::new();
+    too_much.add_recipient_for(contract_id, Beneficiary::new_secret_seal(secret_seal), 101);
+    assert!(matches!(
+        sender.send(too_much, Some(Beneficiary::WitnessVout(1))),
+        Err(WalletError::InsufficientFunds { needed: 101, available: 100, .. })
+    ));
+
+    // Inputs of a send are locked until it's consumed or cancelled, a second
+    // send can't spend them again.
+    let mut first = RgbAssignments::new();
+    first.add_recipient_for(contract_id, Beneficiary::new_secret_seal(secret_seal), 30);
+    let (_, first_commitment, _) = sender.send(first, Some(Beneficiary::WitnessVout(1))).unwrap();
+    let mut second = RgbAssignments::new();
+    second.add_recipient_for(contract_id, Beneficiary::new_secret_seal(secret_seal), 10);
+    assert!(matches!(
+        sender.send(second, Some(Beneficiary::WitnessVout(1))),
+        Err(WalletError::InsufficientFunds { needed: 10, available: 0, .. })
+    ));
+    assert!(sender.cancel_send(&first_commitment));
+    assert!(!sender.cancel_send(&first_commitment));
+
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_secret_seal(secret_seal), 30);
+    let (inputs, commitment, partial_fascia) = sender
+        .send(rgb_assignments, Some(Beneficiary::WitnessVout(1)))
+        .unwrap();
+    assert_eq!(inputs, [genesis_outpoint]);
+
+    let tx = build_rgb_tx(&inputs, 2, &commitment);
+    let spending_txid = tx.txid();
+    receiver
+        .resolver_mut()
+        .add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    // Witness is resolved from the fascia, the sender resolver doesn't know it yet.
+    sender
+        .consume_fascia(partial_fascia.complete_with_tx(&tx.consensus_serialize()))
+        .unwrap();
+
+    assert_eq!(
+        sender.utxos().copied().collect::<Vec<_>>(),
+        [Outpoint::new(spending_txid, 1)]
+    );
+    assert_eq!(sender.balance(contract_id), 70);
+
+    let transfer = rgb_transfer(
+        sender.stock(),
+        contract_id,
+        &[],
+        Some(secret_seal),
+        network,
+    )
+    .unwrap();
+    let transfer = rgb_validate_transfer(transfer, receiver.resolver(), network).unwrap();
+    receiver.accept_transfer(transfer).unwrap();
+
+    assert_eq!(
+        receiver.utxos().copied().collect::<Vec<_>>(),
+        [Outpoint::new(spending_txid, 0)]
+    );
+    assert_eq!(receiver.balance(contract_id), 30);
+}
+
+#[test]
+fn test_rgb_allocations() {
+    use crate::api::rgb_allocations;
+    use crate::types::AllocatedState;
+
+    let (genesis_tx, contract_id, mut resolver, mut stock) = get_test_contract(Network::Regtest);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 30);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        Network::Regtest,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let tx = build_rgb_tx(&available_utxos, 2, &commitment);
+    let spending_txid = tx.txid();
+    resolver.add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock
+        .consume_fascia(partial_fascia.complete_with_tx(&tx.consensus_serialize()), &resolver)
+        .unwrap();
+
+    let outputs = [
+        Outpoint::new(spending_txid, 0),
+        Outpoint::new(spending_txid, 1),
+        Outpoint::new(genesis_txid, 1),
+    ];
+    let allocations = rgb_allocations(&stock, &outputs).unwrap();
+    assert_eq!(allocations.len(), 3);
+    assert!(allocations[&outputs[2]].is_empty());
+    for (outpoint, amount) in [(outputs[0], 30), (outputs[1], 70)] {
+        let [allocation] = allocations[&outpoint].as_slice() else {
+            panic!("expected a single allocation on {outpoint:?}");
+        };
+        assert_eq!(allocation.contract_id, contract_id);
+        assert_eq!(allocation.state, AllocatedState::Amount(amount));
+    }
+    assert_eq!(
+        allocations[&outputs[0]][0].opout.op,
+        allocations[&outputs[1]][0].opout.op,
+    );
+}
+
+#[test]
+fn test_contract_info() {
+    use crate::api::{rgb_contract_info, rgb_list_contracts};
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let allocations = [
+        (format!("opret1st:{genesis_txid}:0"), 100),
+        (format!("opret1st:{genesis_txid}:1"), 23),
+    ];
+    let contract = rgb_issue(
+        "ssi:anonymous", "TEST", "TestCoin", "For tests".into(), 8, allocations, Network::Regtest,
+    );
+    let contract_id: ContractId = contract.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    assert!(rgb_contract_info(&stock, contract_id, Network::Regtest).unwrap().is_none());
+    assert!(rgb_list_contracts(&stock, Network::Regtest).unwrap().is_empty());
+    stock.import_contract(contract, &resolver).unwrap();
+
+    let info = rgb_contract_info(&stock, contract_id, Network::Regtest).unwrap().unwrap();
+    assert_eq!(info.contract_id, contract_id);
+    assert_eq!(info.issuer, "ssi:anonymous");
+    assert_eq!(info.ticker, "TEST");
+    assert_eq!(info.name, "TestCoin");
+    assert_eq!(info.details.as_deref(), Some("For tests"));
+    assert_eq!(info.terms, "");
+    assert_eq!(info.precision, 8);
+    assert_eq!(info.issued_supply, 123);
+    assert_eq!(info.network, Network::Regtest);
+    assert!(info.ifaces.iter().any(|iface| iface == "RGB20Fixed"));
+    assert!(rgb_contract_info(&stock, contract_id, Network::Mainnet).unwrap().is_none());
+
+    assert_eq!(rgb_list_contracts(&stock, Network::Regtest).unwrap(), [info]);
+    assert!(rgb_list_contracts(&stock, Network::Liquid).unwrap().is_empty());
+}
+
+#[test]
+fn test_rgb_amount() {
+    use std::collections::HashSet;
+
+    use rgbinvoice::InvoiceState;
+
+    use crate::api::{
+        rgb_balance_amount, rgb_build_invoice_amount, rgb_contract_info, rgb_issue_amounts,
+    };
+    use crate::error::{AmountError, InvoiceError};
+    use crate::types::RgbAmount;
+
+    let amount = RgbAmount::new(1_234_500_000, 8).unwrap().with_ticker("TEST");
+    assert_eq!(amount.to_string(), "12.345 TEST");
+    assert_eq!(RgbAmount::new(7, 0).unwrap().to_string(), "7");
+    assert_eq!(RgbAmount::new(5, 2).unwrap().to_string(), "0.05");
+
+    let parsed = "12.345 TEST".parse::<RgbAmount>().unwrap();
+    assert_eq!(parsed.precision(), 3);
+    assert_eq!(parsed.ticker(), Some("TEST"));
+    assert_eq!(parsed.clone().rescale(8).unwrap(), amount);
+    assert_eq!(RgbAmount::from_str_with_precision("12.345", 8).unwrap().atomic(), 1_234_500_000);
+    assert_eq!(
+        RgbAmount::from_str_with_precision("0.001", 2),
+        Err(AmountError::TooManyDecimals(2))
+    );
+    assert_eq!(
+        RgbAmount::from_str_with_precision("184467440737.09551616", 8),
+        Err(AmountError::Overflow)
+    );
+    for invalid in ["", "12.", ".5", "1,5", "-1", "1 TEST extra"] {
+        assert_eq!(invalid.parse::<RgbAmount>(), Err(AmountError::InvalidFormat), "{invalid}");
+    }
+
+    let change = amount.checked_sub(&RgbAmount::new(34_500_000, 8).unwrap()).unwrap();
+    assert_eq!(change.to_string(), "12 TEST");
+    assert!(amount.checked_add(&parsed).is_none());
+    assert!(amount.checked_add(&RgbAmount::new(u64::MAX, 8).unwrap()).is_none());
+    assert!(amount.checked_sub(&amount.checked_mul(2).unwrap()).is_none());
+    let other = RgbAmount::new(1, 8).unwrap().with_ticker("OTHER");
+    assert!(amount.checked_add(&other).is_none());
+
+    // Ticker is ignored by equality and hashing.
+    let untagged = RgbAmount::new(1_234_500_000, 8).unwrap();
+    assert_eq!(untagged, amount);
+    assert_ne!(parsed, amount);
+    assert_eq!(HashSet::from([untagged, amount.clone()]).len(), 1);
+
+    // Deserialization checks the precision, same as `RgbAmount::new`.
+    let json = serde_json::to_string(&amount).unwrap();
+    let deserialized = serde_json::from_str::<RgbAmount>(&json).unwrap();
+    assert_eq!(deserialized.ticker(), Some("TEST"));
+    assert_eq!(deserialized, amount);
+    let json = r#"{"atomic":1,"precision":20,"ticker":null}"#;
+    assert!(serde_json::from_str::<RgbAmount>(json).is_err());
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let contract = issue_test_contract(&genesis_tx, 2, Network::Regtest);
+    let contract_id: ContractId = contract.contract_id().into();
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    stock.import_contract(contract, &resolver).unwrap();
+
+    let info = rgb_contract_info(&stock, contract_id, Network::Regtest).unwrap().unwrap();
+    let balance = rgb_balance(
+        &stock,
+        contract_id,
+        &[Outpoint::new(genesis_txid, 0)],
+        Network::Regtest,
+    )
+    .unwrap();
+    assert_eq!(info.amount(balance).to_string(), "1 TEST");
+    assert_eq!(u64::from(info.parse_amount("0.3").unwrap()), 30);
+    assert_eq!(info.parse_amount("0.3 TEST").unwrap(), info.amount(30));
+    assert!(matches!(
+        info.parse_amount("0.3 OTHER"),
+        Err(AmountError::TickerMismatch { .. })
+    ));
+
+    let owned = [Outpoint::new(genesis_txid, 0)];
+    let balance = rgb_balance_amount(&stock, contract_id, &owned, Network::Regtest).unwrap();
+    assert_eq!(balance.to_string(), "1 TEST");
+    assert_eq!(balance, info.amount(100));
+
+    let mut rgb_assignments = RgbAssignments::new();
+    let recipient = Beneficiary::WitnessVout(0);
+    let tenth = "0.1 TEST".parse::<RgbAmount>().unwrap();
+    rgb_assignments.add_amount_for(&info, recipient.clone(), &tenth).unwrap();
+    rgb_assignments.add_amount_for(&info, recipient.clone(), &info.amount(20)).unwrap();
+    assert_eq!(rgb_assignments.0[&contract_id][&recipient], 30);
+    assert_eq!(
+        rgb_assignments.add_amount_for(&info, recipient.clone(), &"0.001".parse().unwrap()),
+        Err(AmountError::TooManyDecimals(2))
+    );
+
+    let invoice = rgb_build_invoice_amount(
+        &mut stock, &info, &tenth, recipient.clone(), [], None, Network::Regtest,
+    )
+    .unwrap();
+    assert_eq!(invoice.owned_state, InvoiceState::Amount(10u64.into()));
+    assert!(matches!(
+        rgb_build_invoice_amount(&mut stock, &info, &other, recipient, [], None, Network::Regtest),
+        Err(InvoiceError::Amount(AmountError::TickerMismatch { .. }))
+    ));
+
+    let allocations = [(format!("opret1st:{genesis_txid}:0"), tenth.clone())];
+    let contract = rgb_issue_amounts(
+        "test", "TEST", "TestCoin", None, 2, allocations, Network::Regtest,
+    )
+    .unwrap();
+    let contract_id: ContractId = contract.contract_id().into();
+    stock.import_contract(contract, &resolver).unwrap();
+    assert_eq!(rgb_balance(&stock, contract_id, &owned, Network::Regtest).unwrap(), 10);
+    let allocations = [(format!("opret1st:{genesis_txid}:0"), tenth)];
+    assert!(matches!(
+        rgb_issue_amounts("test", "OTHER", "Other", None, 2, allocations, Network::Regtest),
+        Err(AmountError::TickerMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_rgb_history() {
+    use rgbstd::vm::WitnessOrd;
+
+    use crate::api::rgb_history;
+
+    let (genesis_tx, contract_id, mut resolver, mut stock) = get_test_contract(Network::Regtest);
+    let genesis_txid = genesis_tx.txid();
+
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 30);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        Network::Regtest,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let tx = build_rgb_tx(&available_utxos, 2, &commitment);
+    let spending_txid = tx.txid();
+    resolver.add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock
+        .consume_fascia(partial_fascia.complete_with_tx(&tx.consensus_serialize()), &resolver)
+        .unwrap();
+
+    let owned = [Outpoint::new(genesis_txid, 0), Outpoint::new(spending_txid, 1)];
+    let history = rgb_history(&stock, contract_id, &owned).unwrap().unwrap();
+    let [genesis, transfer] = history.as_slice() else {
+        panic!("expected genesis and a transfer, got {history:?}");
+    };
+
+    assert_eq!(genesis.witness_txid, None);
+    assert_eq!(genesis.witness_ord, None);
+    assert!(genesis.inputs.is_empty());
+    assert_eq!(genesis.outputs.len(), 1);
+    assert_eq!(genesis.outputs[0].outpoint, owned[0]);
+    assert_eq!(genesis.net_change, 100);
+
+    assert_eq!(transfer.witness_txid, Some(spending_txid.into()));
+    assert!(matches!(transfer.witness_ord, Some(WitnessOrd::Mined(_))));
+    assert_eq!(transfer.inputs, genesis.outputs);
+    assert_eq!(
+        transfer.outputs.iter().map(|a| (a.outpoint, a.amount)).collect::<Vec<_>>(),
+        [(Outpoint::new(spending_txid, 0), 30), (Outpoint::new(spending_txid, 1), 70)]
+    );
+    assert!(transfer.outputs.iter().all(|a| a.opout.op == transfer.opid));
+    assert_eq!(transfer.net_change, -30);
+
+    let history = rgb_history(&stock, contract_id, &[]).unwrap().unwrap();
+    assert!(history.iter().all(|entry| entry.net_change == 0));
+
+    // A transfer spending the change in the same block comes after the first one.
+    let change_utxos = [Outpoint::new(spending_txid, 1)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(contract_id, Beneficiary::new_witness(0), 20);
+    let prev_outputs = rgb_coin_select(&stock, &change_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        Network::Regtest,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&change_utxos, ti_list);
+    let next_tx = build_rgb_tx(&change_utxos, 2, &commitment);
+    resolver.add_onchain_tx(&next_tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock
+        .consume_fascia(partial_fascia.complete_with_tx(&next_tx.consensus_serialize()), &resolver)
+        .unwrap();
+    let history = rgb_history(&stock, contract_id, &owned).unwrap().unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[1].opid, transfer.opid);
+    assert_eq!(history[2].witness_txid, Some(next_tx.txid().into()));
+    assert_eq!(history[2].net_change, -70);
+
+    // Outputs of archived transfers are still listed, but they moved nothing.
+    let mut replaced = LnResolver::new();
+    replaced.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    replaced.replace_active(&tx.consensus_serialize());
+    replaced.replace_active(&next_tx.consensus_serialize());
+    replaced.replace_active(&build_rgb_tx(&available_utxos, 2, &[0; 32]).consensus_serialize());
+    stock.update_witnesses(&replaced, 0).unwrap();
+    let archived = rgb_history(&stock, contract_id, &owned).unwrap().unwrap();
+    assert_eq!(archived.len(), 3);
+    assert_eq!(archived[1].witness_ord, Some(WitnessOrd::Archived));
+    assert_eq!(archived[1].outputs, transfer.outputs);
+    assert_eq!(archived[1].inputs, transfer.inputs);
+    assert_eq!(archived[2].witness_txid, Some(next_tx.txid().into()));
+    assert!(archived[1..].iter().all(|entry| entry.net_change == 0));
+
+    let unknown = ContractId::from([0; 32]);
+    assert!(rgb_history(&stock, unknown, &owned).unwrap().is_none());
+}
+
+#[test]
+fn test_issue_request() {
+    use crate::api::{rgb_contract_info, rgb_issue_from_request};
+    use crate::error::IssueError;
+    use crate::issue::IssueRequest;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let dir = tempfile::tempdir().unwrap();
+    let media_path = dir.path().join("terms.pdf");
+    std::fs::write(&media_path, b"%PDF terms").unwrap();
+
+    let yaml = format!(
+        r#"
+issuer: test
+ticker: TEST
+name: TestCoin
+details: For tests
+precision: 2
+terms: The holder may redeem the token.
+media:
+  path: {path}
+  mime: application/pdf
+allocations:
+  - txid: "{genesis_txid}"
+    vout: 0
+    amount: 100
+  - close_method: opret1st
+    txid: "{genesis_txid}"
+    vout: 1
+    amount: 50
+network: regtest
+"#,
+        path = media_path.display(),
+    );
+    let json = format!(
+        r#"{{
+    "issuer": "test", "ticker": "TEST", "name": "TestCoin", "details": "For tests",
+    "precision": 2, "terms": "The holder may redeem the token.",
+    "media": {{ "path": "{path}", "mime": "application/pdf" }},
+    "allocations": [
+        {{ "txid": "{genesis_txid}", "vout": 0, "amount": 100 }},
+        {{ "close_method": "opret1st", "txid": "{genesis_txid}", "vout": 1, "amount": 50 }}
+    ],
+    "network": "regtest"
+}}"#,
+        path = media_path.display(),
+    );
+    let toml = format!(
+        r#"
+issuer = "test"
+ticker = "TEST"
+name = "TestCoin"
+details = "For tests"
+precision = 2
+terms = "The holder may redeem the token."
+network = "regtest"
+
+[media]
+path = "{path}"
+mime = "application/pdf"
+
+[[allocations]]
+txid = "{genesis_txid}"
+vout = 0
+amount = 100
+
+[[allocations]]
+close_method = "opret1st"
+txid = "{genesis_txid}"
+vout = 1
+amount = 50
+"#,
+        path = media_path.display(),
+    );
+
+    let request = IssueRequest::from_yaml(&yaml).unwrap();
+    assert_eq!(IssueRequest::from_json(&json).unwrap(), request);
+    assert_eq!(IssueRequest::from_toml(&toml).unwrap(), request);
+    request.validate().unwrap();
+
+    let contract = rgb_issue_from_request(&request).unwrap();
+    let contract_id: ContractId = contract.contract_id().into();
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    stock.import_contract(contract, &resolver).unwrap();
+
+    let info = rgb_contract_info(&stock, contract_id, Network::Regtest).unwrap().unwrap();
+    assert_eq!(info.ticker, "TEST");
+    assert_eq!(info.precision, 2);
+    assert_eq!(info.issued_supply, 150);
+    assert_eq!(info.terms, "The holder may redeem the token.");
+    assert_eq!(info.network, Network::Regtest);
+    let owned = [Outpoint::new(genesis_txid, 1)];
+    assert_eq!(rgb_balance(&stock, contract_id, &owned, Network::Regtest).unwrap(), 50);
+
+    let mut invalid = request.clone();
+    invalid.ticker = "TOOLONGTICKER".to_string();
+    invalid.precision = 19;
+    invalid.allocations[1].txid = "xyz".to_string();
+    invalid.allocations[1].close_method = "tapret1st".to_string();
+    invalid.allocations[0].amount = u64::MAX;
+    let Err(IssueError::Invalid(errors)) = invalid.validate() else {
+        panic!("request must be invalid");
+    };
+    let fields = errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            "ticker",
+            "precision",
+            "allocations[1].close_method",
+            "allocations[1].txid",
+            "allocations",
+        ]
+    );
+    assert!(matches!(rgb_issue_from_request(&invalid), Err(IssueError::Invalid(_))));
+
+    assert!(matches!(
+        IssueRequest::from_yaml("ticker: TEST\nunknown: 1\n"),
+        Err(IssueError::Parse(_))
+    ));
+    assert!(matches!(
+        IssueRequest::from_yaml(&yaml.replace("network: regtest", "network: moonnet")),
+        Err(IssueError::Parse(_))
+    ));
+}
+
+#[test]
+fn test_contract_terms() {
+    use crate::api::{rgb_contract_terms, rgb_issue_with_terms, rgb_verify_contract_media};
+    use crate::error::IssueError;
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let allocations = || [(format!("opret1st:{genesis_txid}:0"), 100)];
+    let text = "The holder may redeem the token.";
+    let file = b"%PDF terms";
+
+    let contract = rgb_issue_with_terms(
+        "test", "TEST", "TestCoin", None, 8, text,
+        Some(("application/pdf", file)), allocations(), Network::Regtest,
+    )
+    .unwrap();
+    let contract_id: ContractId = contract.contract_id().into();
+    let plain = rgb_issue("test", "PLAIN", "PlainCoin", None, 8, allocations(), Network::Regtest);
+    let plain_id: ContractId = plain.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    assert!(rgb_contract_terms(&stock, contract_id).is_none());
+    stock.import_contract(contract, &resolver).unwrap();
+    stock.import_contract(plain, &resolver).unwrap();
+
+    let terms = rgb_contract_terms(&stock, contract_id).unwrap();
+    assert_eq!(terms.text, text);
+    let media = terms.media.unwrap();
+    assert_eq!(media.mime, "application/pdf");
+    assert!(media.verify(file));
+    assert!(rgb_verify_contract_media(&stock, contract_id, file));
+    assert!(!rgb_verify_contract_media(&stock, contract_id, b"%PDF forged"));
+
+    let terms = rgb_contract_terms(&stock, plain_id).unwrap();
+    assert_eq!(terms.text, "");
+    assert!(terms.media.is_none());
+    assert!(!rgb_verify_contract_media(&stock, plain_id, file));
+
+    let Err(IssueError::Invalid(errors)) = rgb_issue_with_terms(
+        "test", "TEST", "TestCoin", None, 8, text,
+        Some(("pdf", file)), allocations(), Network::Regtest,
+    ) else {
+        panic!("media type must be invalid");
+    };
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "media.mime");
+}
+
+#[test]
+fn test_network() {
+    use crate::api::{
+        rgb_build_invoice, rgb_check_contract, rgb_check_invoice, rgb_export_contract,
+        rgb_validate_contract,
+    };
+    use crate::error::{NetworkError, TransferError, ValidationError};
+
+    assert_eq!("testnet".parse::<Network>().unwrap(), Network::Testnet3);
+    assert_eq!("Signet".parse::<Network>().unwrap(), Network::Signet);
+    assert_eq!(Network::Testnet4.to_string().parse::<Network>().unwrap(), Network::Testnet4);
+    assert_eq!(
+        "moonnet".parse::<Network>(),
+        Err(NetworkError::Unknown("moonnet".to_string()))
+    );
+
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let allocations = || [(format!("opret1st:{genesis_txid}:0"), 100)];
+    let mainnet = rgb_issue("test", "TEST", "TestCoin", None, 8, allocations(), Network::Mainnet);
+    let mainnet_id: ContractId = mainnet.contract_id().into();
+    let testnet = rgb_issue("test", "TEST", "TestCoin", None, 8, allocations(), Network::Testnet3);
+    let testnet_id: ContractId = testnet.contract_id().into();
+    let liquid = rgb_issue("test", "TEST", "TestCoin", None, 8, allocations(), Network::Liquid);
+    let liquid_id: ContractId = liquid.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    stock.import_contract(mainnet, &resolver).unwrap();
+    stock.import_contract(testnet, &resolver).unwrap();
+    stock.import_contract(liquid, &resolver).unwrap();
+
+    rgb_check_contract(&stock, mainnet_id, Network::Mainnet).unwrap();
+    rgb_check_contract(&stock, testnet_id, Network::Testnet4).unwrap();
+    let mismatch = |contract_id, network| NetworkError::ContractMismatch { contract_id, network };
+    assert_eq!(
+        rgb_check_contract(&stock, mainnet_id, Network::Regtest),
+        Err(mismatch(mainnet_id, Network::Regtest))
+    );
+    assert_eq!(
+        rgb_check_contract(&stock, mainnet_id, Network::Liquid),
+        Err(mismatch(mainnet_id, Network::Liquid))
+    );
+    assert_eq!(
+        rgb_check_contract(&stock, testnet_id, Network::Mainnet),
+        Err(mismatch(testnet_id, Network::Mainnet))
+    );
+    rgb_check_contract(&stock, liquid_id, Network::Liquid).unwrap();
+    assert_eq!(
+        rgb_check_contract(&stock, liquid_id, Network::Mainnet),
+        Err(mismatch(liquid_id, Network::Mainnet))
+    );
+
+    let owned = [Outpoint::new(genesis_txid, 0)];
+    assert_eq!(rgb_balance(&stock, testnet_id, &owned, Network::Regtest), Ok(100));
+    assert_eq!(
+        rgb_balance(&stock, testnet_id, &owned, Network::Mainnet),
+        Err(mismatch(testnet_id, Network::Mainnet))
+    );
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(testnet_id, Beneficiary::new_witness(0), 30);
+    let change = Some(Beneficiary::WitnessVout(1));
+    assert_eq!(
+        rgb_compose(&stock, owned, rgb_assignments, change, Network::Mainnet),
+        Err(TransferError::Network(mismatch(testnet_id, Network::Mainnet)))
+    );
+    assert_eq!(
+        rgb_transfer(&stock, testnet_id, &owned, None, Network::Mainnet).unwrap_err(),
+        TransferError::Network(mismatch(testnet_id, Network::Mainnet))
+    );
+
+    let contract = rgb_export_contract(&stock, mainnet_id);
+    assert!(matches!(
+        rgb_validate_contract(contract.clone(), &resolver, Network::Signet),
+        Err(ValidationError::Network(NetworkError::ContractMismatch { .. }))
+    ));
+    rgb_validate_contract(contract, &resolver, Network::Mainnet).unwrap();
+
+    let beneficiary = Beneficiary::WitnessVout(0);
+    assert_eq!(
+        rgb_build_invoice(&mut stock, mainnet_id, 10, beneficiary.clone(), [], None, Network::Regtest)
+            .unwrap_err(),
+        mismatch(mainnet_id, Network::Regtest)
+    );
+    let invoice =
+        rgb_build_invoice(&mut stock, mainnet_id, 10, beneficiary, [], None, Network::Mainnet)
+            .unwrap();
+    rgb_check_invoice(&stock, &invoice, Network::Mainnet).unwrap();
+    assert_eq!(
+        rgb_check_invoice(&stock, &invoice, Network::Signet),
+        Err(NetworkError::InvoiceMismatch {
+            expected: Network::Signet,
+            found: rgbinvoice::ChainNet::BitcoinMainnet,
+        })
+    );
+}
+
+#[test]
+fn test_rgb_transfer_many() {
+    use crate::api::{
+        rgb_build_invoice, rgb_contract_info, rgb_transfer_many, rgb_validate_transfer,
+    };
+    use crate::error::TransferError;
+
+    let network = Network::Regtest;
+    let genesis_tx = get_first_tx();
+    let genesis_txid = genesis_tx.txid();
+    let allocations = || [(format!("opret1st:{genesis_txid}:0"), 100)];
+    let first = rgb_issue("test", "FIRST", "FirstCoin", None, 8, allocations(), network);
+    let first_id: ContractId = first.contract_id().into();
+    let second = rgb_issue("test", "SECOND", "SecondCoin", None, 8, allocations(), network);
+    let second_id: ContractId = second.contract_id().into();
+
+    let mut resolver = LnResolver::new();
+    resolver.add_onchain_tx(&genesis_tx.consensus_serialize(), 1, GENESIS_TIMESTAMP);
+    let mut stock = get_stock();
+    stock.import_contract(first, &resolver).unwrap();
+    stock.import_contract(second, &resolver).unwrap();
+
+    let mut receiver = get_stock();
+    let invoice = rgb_build_invoice(
+        &mut receiver,
+        first_id,
+        30,
+        Beneficiary::WitnessVout(0),
+        [],
+        None,
+        network,
+    )
+    .unwrap();
+    let secret_seal = match invoice.beneficiary.into_inner() {
+        rgbinvoice::Beneficiary::BlindedSeal(seal) => seal.to_byte_array(),
+        _ => unreachable!(),
+    };
+
+    // Only the first contract is sent, the second one is moved to the change
+    // by a blank transition of the same witness.
+    let available_utxos = [Outpoint::new(genesis_txid, 0)];
+    let mut rgb_assignments = RgbAssignments::new();
+    rgb_assignments.add_recipient_for(first_id, Beneficiary::new_secret_seal(secret_seal), 30);
+    let prev_outputs = rgb_coin_select(&stock, &available_utxos, &rgb_assignments);
+    let ti_list = rgb_compose(
+        &stock,
+        prev_outputs,
+        rgb_assignments,
+        Some(Beneficiary::WitnessVout(1)),
+        network,
+    )
+    .unwrap();
+    let (commitment, partial_fascia) = rgb_commit(&available_utxos, ti_list);
+    let tx = build_rgb_tx(&available_utxos, 2, &commitment);
+    let spending_txid = tx.txid();
+    resolver.add_onchain_tx(&tx.consensus_serialize(), 2, GENESIS_TIMESTAMP + 1);
+    stock
+        .consume_fascia(partial_fascia.complete_with_tx(&tx.consensus_serialize()), &resolver)
+        .unwrap();
+
+    let payment = Outpoint::new(spending_txid, 0);
+    let change = Outpoint::new(spending_txid, 1);
+    assert!(rgb_transfer_many(&stock, &[], network).unwrap().is_empty());
+    let to_receiver = [(first_id, vec![], Some(secret_seal))];
+    assert!(matches!(
+        rgb_transfer_many(&stock, &to_receiver, Network::Mainnet),
+        Err(TransferError::Network(_))
+    ));
+
+    // The second contract only moved to the change, so the receiver doesn't
+    // get it.
+    let transfers = rgb_transfer_many(&stock, &to_receiver, network).unwrap();
+    assert_eq!(transfers.keys().copied().collect::<Vec<_>>(), [first_id]);
+    for transfer in transfers.into_values() {
+        let transfer = rgb_validate_transfer(transfer, &resolver, network).unwrap();
+        receiver.accept_transfer(transfer, &resolver).unwrap();
+    }
+    assert_eq!(rgb_balance(&receiver, first_id, &[payment], network).unwrap(), 30);
+    assert_eq!(rgb_contract_info(&receiver, second_id, network).unwrap(), None);
+
+    // Whoever owns the change gets the second contract moved there by the
+    // blank transition too, even if only the first one is requested.
+    let mut change_owner = get_stock();
+    let transfers = rgb_transfer_many(&stock, &[(first_id, vec![change], None)], network).unwrap();
+    let mut contract_ids = [first_id, second_id];
+    contract_ids.sort();
+    assert_eq!(transfers.keys().copied().collect::<Vec<_>>(), contract_ids);
+    for transfer in transfers.into_values() {
+        let transfer = rgb_validate_transfer(transfer, &resolver, network).unwrap();
+        change_owner.accept_transfer(transfer, &resolver).unwrap();
+    }
+    assert_eq!(rgb_balance(&change_owner, first_id, &[change], network).unwrap(), 70);
+    assert_eq!(rgb_balance(&change_owner, second_id, &[change], network).unwrap(), 100);
+}
+
+#[test]
+fn test_consignment_files() {
+    use crate::api::{
+        rgb_armor_contract, rgb_armor_transfer, rgb_consignment_file_name, rgb_export_contract,
+        rgb_read_contract, rgb_read_transfer, rgb_unarmor_contract, rgb_unarmor_transfer,
+        rgb_validate_contract, rgb_write_contract, rgb_write_transfer,
+    };
+    use crate::error::{ConsignmentError, ValidationError};
+    use crate::resolvers::LocalResolver;
+
+    let network = Network::Regtest;
+    let (genesis_tx, contract_id, resolver, stock) = get_test_contract(network);
+    let contract = rgb_export_contract(&stock, contract_id);
+
+    let mut file = vec![];
+    rgb_write_contract(&contract, &mut file).unwrap();
+    let read = rgb_read_contract(file.as_slice(), &resolver, network).unwrap();
+    assert_eq!(read.consignment_id(), contract.consignment_id());
+    assert!(matches!(
+        rgb_read_contract(file.as_slice(), &resolver, Network::Mainnet),
+        Err(ConsignmentError::Invalid(ValidationError::Network(_)))
+    ));
+    assert!(matches!(
+        rgb_read_transfer(file.as_slice(), &resolver, network),
+        Err(ConsignmentError::InvalidData(_))
+    ));
+    assert!(matches!(
+        rgb_read_contract(&file[..file.len() / 2], &resolver, network),
+        Err(ConsignmentError::InvalidData(_))
+    ));
+
+    let armored = rgb_armor_contract(&contract);
+    assert!(armored.starts_with("-----BEGIN RGB CONSIGNMENT-----"));
+    assert!(armored.contains(&format!("Contract: {}", contract.contract_id())));
+    assert!(armored.contains(&format!("Id: {}", contract.consignment_id())));
+    let read = rgb_unarmor_contract(&armored, &resolver, network).unwrap();
+    assert_eq!(read.consignment_id(), contract.consignment_id());
+    assert!(matches!(
+        rgb_unarmor_transfer(&armored, &resolver, network),
+        Err(ConsignmentError::InvalidData(_))
+    ));
+
+    let headers = armored.lines().take_while(|line| !line.is_empty()).collect::<Vec<_>>();
+    let checksum = headers.iter().find(|line| line.starts_with("Check-SHA256: ")).unwrap();
+    let zeros = format!("Check-SHA256: {}", "0".repeat(64));
+    assert!(matches!(
+        rgb_unarmor_contract(&armored.replace(checksum, &zeros), &resolver, network),
+        Err(ConsignmentError::ChecksumMismatch)
+    ));
+    assert!(matches!(
+        rgb_unarmor_contract(&armored.replace(&format!("{checksum}\n"), ""), &resolver, network),
+        Err(ConsignmentError::MissingChecksum)
+    ));
+    let contract_header = headers.iter().find(|line| line.starts_with("Contract: ")).unwrap();
+    let other_contract = contract_header.replace("Contract: ", "Contract: x");
+    assert!(matches!(
+        rgb_unarmor_contract(&armored.replace(contract_header, &other_contract), &resolver, network),
+        Err(ConsignmentError::HeaderMismatch(title)) if title == "Contract"
+    ));
+
+    let file_name = rgb_consignment_file_name(&contract, false);
+    assert!(file_name.ends_with(".rgb"));
+    assert!(!file_name.contains([':', '#', '/']));
+    assert_eq!(rgb_consignment_file_name(&contract, true), format!("{file_name}a"));
+
+    let contract = rgb_validate_contract(contract, &resolver, network).unwrap();
+    let (_, transfer) = basic_transfer(genesis_tx, contract, network);
+    let transfer = transfer.into_consignment();
+    let resolver = LocalResolver::with_consignment(&transfer);
+
+    let mut file = vec![];
+    rgb_write_transfer(&transfer, &mut file).unwrap();
+    let read = rgb_read_transfer(file.as_slice(), &resolver, network).unwrap();
+    assert_eq!(read.consignment_id(), transfer.consignment_id());
+    assert!(matches!(
+        rgb_read_contract(file.as_slice(), &resolver, network),
+        Err(ConsignmentError::InvalidData(_))
+    ));
+
+    let armored = rgb_armor_transfer(&transfer);
+    assert!(armored.contains("Type: transfer"));
+    let read = rgb_unarmor_transfer(&armored, &resolver, network).unwrap();
+    assert_eq!(read.consignment_id(), transfer.consignment_id());
+    assert!(rgb_consignment_file_name(&transfer, true).ends_with(".rgba"));
+}
@@ -6,13 +6,17 @@ use std::str::FromStr;
 pub(crate) use crate::detail::{
     Beneficiary as RawBeneficiary, RgbAssignments as RawRgbAssignments,
 };
+use amplify::Wrapper;
 use rand::Rng;
 pub(crate) use rgbstd::{
-    containers::TransitionInfo as RawTransitionInfo, ContractId as RawContractId, Txid as RawTxid,
-    XChain, XOutpoint as RawOutpoint, 
+    containers::TransitionInfo as RawTransitionInfo, stl::ContractTerms as RawContractTerms, ContractId as RawContractId, Txid as RawTxid,
+    Opout as RawOpout, XChain, XOutpoint as RawOutpoint,
     SecretSeal,
 };
 use bp::seals::txout::CloseMethod;
+use crate::error::{AmountError, NetworkError};
+use rgbinvoice::ChainNet;
+use rgbstd::vm::WitnessOrd;
 use rgbstd::GraphSeal;
 
 use serde::Deserialize;
@@ -45,9 +49,9 @@ impl From<[u8; 32]> for Txid {
     }
 }
 
-impl Into<[u8; 32]> for Txid {
-    fn into(self) -> [u8; 32] {
-        self.0.as_ref().to_byte_array()
+impl From<Txid> for [u8; 32] {
+    fn from(value: Txid) -> Self {
+        value.0.as_ref().to_byte_array()
     }
 }
 
@@ -76,9 +80,9 @@ impl From<[u8; 32]> for ContractId {
     }
 }
 
-impl Into<[u8; 32]> for ContractId {
-    fn into(self) -> [u8; 32] {
-        self.0.as_ref().to_byte_array()
+impl From<ContractId> for [u8; 32] {
+    fn from(value: ContractId) -> Self {
+        value.0.as_ref().to_byte_array()
     }
 }
 
@@ -105,6 +109,88 @@ impl ToRaw for ContractId {
     }
 }
 
+/// Network contracts are issued on and invoices and transfers belong to.
+///
+/// Genesis only tells mainnet contracts apart from test ones, so a contract
+/// issued on any of the test networks is accepted on all of them.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[serde(alias = "bitcoin")]
+    Mainnet,
+    #[serde(alias = "testnet")]
+    Testnet3,
+    Testnet4,
+    Signet,
+    Regtest,
+    Liquid,
+}
+
+impl Network {
+    pub fn is_testnet(self) -> bool {
+        match self {
+            Self::Mainnet | Self::Liquid => false,
+            Self::Testnet3 | Self::Testnet4 | Self::Signet | Self::Regtest => true,
+        }
+    }
+
+    /// Network of the invoice beneficiaries, both testnets share the same one.
+    pub fn chain_net(self) -> ChainNet {
+        match self {
+            Self::Mainnet => ChainNet::BitcoinMainnet,
+            Self::Testnet3 | Self::Testnet4 => ChainNet::BitcoinTestnet,
+            Self::Signet => ChainNet::BitcoinSignet,
+            Self::Regtest => ChainNet::BitcoinRegtest,
+            Self::Liquid => ChainNet::LiquidMainnet,
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet3 => "testnet3",
+            Self::Testnet4 => "testnet4",
+            Self::Signet => "signet",
+            Self::Regtest => "regtest",
+            Self::Liquid => "liquid",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Network {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" | "bitcoin" => Ok(Self::Mainnet),
+            "testnet3" | "testnet" => Ok(Self::Testnet3),
+            "testnet4" => Ok(Self::Testnet4),
+            "signet" => Ok(Self::Signet),
+            "regtest" => Ok(Self::Regtest),
+            "liquid" => Ok(Self::Liquid),
+            _ => Err(NetworkError::Unknown(s.to_owned())),
+        }
+    }
+}
+
+impl TryFrom<Network> for bitcoin::Network {
+    type Error = NetworkError;
+
+    fn try_from(network: Network) -> Result<Self, Self::Error> {
+        match network {
+            Network::Mainnet => Ok(Self::Bitcoin),
+            Network::Testnet3 => Ok(Self::Testnet),
+            Network::Testnet4 => Ok(Self::Testnet4),
+            Network::Signet => Ok(Self::Signet),
+            Network::Regtest => Ok(Self::Regtest),
+            Network::Liquid => Err(NetworkError::Unsupported(network)),
+        }
+    }
+}
+
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Outpoint {
@@ -162,6 +248,7 @@ impl Beneficiary {
         Self::SecretSeal(secret_seal)
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub(crate) fn to_raw_with_blinding(self, blinding: u64) -> RawBeneficiary {
         let revealed_seal = |seal| -> RawBeneficiary {
             From::<XChain<GraphSeal>>::from(XChain::with(rgbstd::Layer1::Bitcoin, seal))
@@ -197,8 +284,380 @@ impl ToRaw for TransitionInfo {
 }
 
 
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Opout {
+    pub op: [u8; 32],
+    pub ty: u16,
+    pub no: u16,
+}
+
+impl From<RawOpout> for Opout {
+    fn from(opout: RawOpout) -> Self {
+        Self {
+            op: opout.op.to_byte_array(),
+            ty: opout.ty.to_inner(),
+            no: opout.no,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AllocatedState {
+    Void,
+    Amount(u64),
+    Data(Vec<u8>),
+    Attachment { id: [u8; 32], media_type: String },
+}
+
+
+/// State assigned by a contract operation output to an outpoint.
+#[derive(Debug, Clone, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Allocation {
+    pub contract_id: ContractId,
+    pub opout: Opout,
+    pub state: AllocatedState,
+}
+
+
+pub(crate) const MAX_PRECISION: u8 = 18;
+
+/// Amount in atomic units together with the precision of its contract.
+///
+/// Formats as a decimal number followed by the ticker, if any, e.g.
+/// `12.345 TEST` for 1234500000 atomic units with a precision of 8.
+///
+/// The ticker is only a label: amounts are compared and hashed by their
+/// atomic value and precision, but amounts with different tickers can't be
+/// added or subtracted.
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedRgbAmount")]
+pub struct RgbAmount {
+    atomic: u64,
+    precision: u8,
+    ticker: Option<String>,
+}
+
+// Deserialized form of `RgbAmount`, before its precision is checked.
+#[derive(Deserialize)]
+struct UncheckedRgbAmount {
+    atomic: u64,
+    precision: u8,
+    ticker: Option<String>,
+}
+
+impl TryFrom<UncheckedRgbAmount> for RgbAmount {
+    type Error = AmountError;
+
+    fn try_from(amount: UncheckedRgbAmount) -> Result<Self, Self::Error> {
+        let mut checked = Self::new(amount.atomic, amount.precision)?;
+        checked.ticker = amount.ticker;
+        Ok(checked)
+    }
+}
+
+impl RgbAmount {
+    pub fn new(atomic: u64, precision: u8) -> Result<Self, AmountError> {
+        if precision > MAX_PRECISION {
+            return Err(AmountError::InvalidPrecision(precision));
+        }
+        Ok(Self {
+            atomic,
+            precision,
+            ticker: None,
+        })
+    }
+
+    pub fn with_ticker(mut self, ticker: impl Into<String>) -> Self {
+        self.ticker = Some(ticker.into());
+        self
+    }
+
+    /// Parses a decimal amount, scaling it to `precision`.
+    pub fn from_str_with_precision(s: &str, precision: u8) -> Result<Self, AmountError> {
+        s.parse::<Self>()?.rescale(precision)
+    }
+
+    pub fn atomic(&self) -> u64 {
+        self.atomic
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn ticker(&self) -> Option<&str> {
+        self.ticker.as_deref()
+    }
+
+    /// Expresses the same amount with another precision, failing if it would
+    /// lose decimals or overflow.
+    pub fn rescale(self, precision: u8) -> Result<Self, AmountError> {
+        if precision > MAX_PRECISION {
+            return Err(AmountError::InvalidPrecision(precision));
+        }
+        let atomic = if precision >= self.precision {
+            let scale = 10u64.pow((precision - self.precision) as u32);
+            self.atomic.checked_mul(scale).ok_or(AmountError::Overflow)?
+        } else {
+            let scale = 10u64.pow((self.precision - precision) as u32);
+            if !self.atomic.is_multiple_of(scale) {
+                return Err(AmountError::TooManyDecimals(precision));
+            }
+            self.atomic / scale
+        };
+        Ok(Self {
+            atomic,
+            precision,
+            ticker: self.ticker,
+        })
+    }
+
+    // Atomic units for a contract, failing if the ticker differs from its own.
+    pub(crate) fn to_atomic(&self, precision: u8, ticker: &str) -> Result<u64, AmountError> {
+        match self.ticker() {
+            Some(found) if found != ticker => Err(AmountError::TickerMismatch {
+                expected: ticker.to_owned(),
+                found: found.to_owned(),
+            }),
+            _ => Ok(self.clone().rescale(precision)?.atomic),
+        }
+    }
+
+    // Amounts of different assets or precisions can't be combined.
+    fn is_compatible(&self, other: &Self) -> bool {
+        self.precision == other.precision
+            && (self.ticker.is_none() || other.ticker.is_none() || self.ticker == other.ticker)
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        if !self.is_compatible(other) {
+            return None;
+        }
+        Some(Self {
+            atomic: self.atomic.checked_add(other.atomic)?,
+            precision: self.precision,
+            ticker: self.ticker.clone().or_else(|| other.ticker.clone()),
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        if !self.is_compatible(other) {
+            return None;
+        }
+        Some(Self {
+            atomic: self.atomic.checked_sub(other.atomic)?,
+            precision: self.precision,
+            ticker: self.ticker.clone().or_else(|| other.ticker.clone()),
+        })
+    }
+
+    pub fn checked_mul(&self, factor: u64) -> Option<Self> {
+        Some(Self {
+            atomic: self.atomic.checked_mul(factor)?,
+            precision: self.precision,
+            ticker: self.ticker.clone(),
+        })
+    }
+}
+
+impl PartialEq for RgbAmount {
+    fn eq(&self, other: &Self) -> bool {
+        self.atomic == other.atomic && self.precision == other.precision
+    }
+}
+
+impl std::hash::Hash for RgbAmount {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.atomic.hash(state);
+        self.precision.hash(state);
+    }
+}
+
+impl From<RgbAmount> for u64 {
+    fn from(amount: RgbAmount) -> Self {
+        amount.atomic
+    }
+}
+
+impl std::fmt::Display for RgbAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Beyond u64 the whole amount is fractional.
+        let (int, fract) = match 10u64.checked_pow(self.precision as u32) {
+            Some(scale) => (self.atomic / scale, self.atomic % scale),
+            None => (0, self.atomic),
+        };
+        write!(f, "{int}")?;
+        if fract != 0 {
+            let fract = format!("{:0width$}", fract, width = self.precision as usize);
+            write!(f, ".{}", fract.trim_end_matches('0'))?;
+        }
+        if let Some(ticker) = &self.ticker {
+            write!(f, " {ticker}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RgbAmount {
+    type Err = AmountError;
+
+    /// Parses `12.345` or `12.345 TEST`, the precision is the number of
+    /// decimals given.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let number = parts.next().ok_or(AmountError::InvalidFormat)?;
+        let ticker = parts.next().map(str::to_owned);
+        if parts.next().is_some() {
+            return Err(AmountError::InvalidFormat);
+        }
+
+        let (int, fract) = number.split_once('.').unwrap_or((number, ""));
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !is_digits(int) || !is_digits(fract) || number.ends_with('.') {
+            return Err(AmountError::InvalidFormat);
+        }
+        if fract.len() > MAX_PRECISION as usize {
+            return Err(AmountError::TooManyDecimals(MAX_PRECISION));
+        }
+        let precision = fract.len() as u8;
+
+        let atomic = format!("{int}{fract}")
+            .parse::<u64>()
+            .map_err(|_| AmountError::Overflow)?;
+
+        Ok(Self {
+            atomic,
+            precision,
+            ticker,
+        })
+    }
+}
+
+
+/// Digest and MIME type of a file attached to the contract terms.
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Media {
+    pub mime: String,
+    pub digest: [u8; 32],
+}
+
+impl Media {
+    /// Checks that `file` is the one committed in genesis.
+    pub fn verify(&self, file: &[u8]) -> bool {
+        use bitcoin::hashes::{sha256, Hash};
+
+        sha256::Hash::hash(file).to_byte_array() == self.digest
+    }
+}
+
+
+/// Ricardian contract text and media committed in genesis.
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Terms {
+    pub text: String,
+    pub media: Option<Media>,
+}
+
+impl From<RawContractTerms> for Terms {
+    fn from(terms: RawContractTerms) -> Self {
+        Self {
+            text: terms.text.to_string(),
+            media: terms.media.map(|media| Media {
+                mime: media.ty.to_string(),
+                digest: media.digest.to_byte_array(),
+            }),
+        }
+    }
+}
+
+
+/// Genesis and RGB20 global state of a contract.
+///
+/// The genesis only records whether a contract is a mainnet, testnet or liquid
+/// one, so `network` is the one the contract was looked up for, e.g. any of
+/// the test networks for a testnet contract.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ContractInfo {
+    pub contract_id: ContractId,
+    pub issuer: String,
+    pub ticker: String,
+    pub name: String,
+    pub details: Option<String>,
+    pub terms: String,
+    pub precision: u8,
+    pub issued_supply: u64,
+    pub genesis_timestamp: i64,
+    pub network: Network,
+    pub ifaces: Vec<String>,
+}
+
+impl ContractInfo {
+    pub fn amount(&self, atomic: u64) -> RgbAmount {
+        RgbAmount::new(atomic, self.precision)
+            .expect("contract precision is valid")
+            .with_ticker(self.ticker.clone())
+    }
+
+    /// Parses an amount of this contract, e.g. `12.345` or `12.345 TEST`.
+    pub fn parse_amount(&self, s: &str) -> Result<RgbAmount, AmountError> {
+        let amount: RgbAmount = s.parse()?;
+        let atomic = amount.to_atomic(self.precision, &self.ticker)?;
+        Ok(self.amount(atomic))
+    }
+
+    /// Atomic units of an amount of this contract, rescaled to its precision.
+    pub fn atomic(&self, amount: &RgbAmount) -> Result<u64, AmountError> {
+        amount.to_atomic(self.precision, &self.ticker)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HistoryAllocation {
+    pub opout: Opout,
+    pub outpoint: Outpoint,
+    pub amount: u64,
+}
+
+
+/// Contract operation as seen from a set of owned outpoints.
+///
+/// Only the allocations whose seals are known to the stock are listed, those
+/// to the concealed seals of other parties are left out.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HistoryEntry {
+    pub opid: [u8; 32],
+    // None for genesis.
+    pub witness_txid: Option<Txid>,
+    pub witness_ord: Option<WitnessOrd>,
+    pub inputs: Vec<HistoryAllocation>,
+    pub outputs: Vec<HistoryAllocation>,
+    // Received minus spent by the owned outpoints, zero for archived operations.
+    pub net_change: i128,
+}
+
+
+/// Balance of a contract on an outpoint which changed after a witness update.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub contract_id: ContractId,
+    pub outpoint: Outpoint,
+    pub before: u64,
+    pub after: u64,
+}
+
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WitnessUpdateReport {
+    pub balance_changes: Vec<BalanceChange>,
+    // Witnesses which couldn't be resolved and kept their previous ordering.
+    pub failed: BTreeMap<Txid, String>,
+}
+
+
 // Use BTreeMap to have a consistent order for generating blinding factors
-#[derive(Debug, Hash, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Hash, Clone, Serialize, Deserialize)]
 pub struct RgbAssignments(pub(crate) BTreeMap<ContractId, BTreeMap<Beneficiary, u64>>);
 
 impl RgbAssignments {
@@ -232,6 +691,20 @@ impl RgbAssignments {
         }
     }
 
+    /// Adds a recipient of an amount of `contract`, e.g. one parsed with
+    /// `ContractInfo::parse_amount`.
+    pub fn add_amount_for(
+        &mut self,
+        contract: &ContractInfo,
+        recipient: Beneficiary,
+        amount: &RgbAmount,
+    ) -> Result<(), AmountError> {
+        let atomic = contract.atomic(amount)?;
+        self.add_recipient_for(contract.contract_id, recipient, atomic);
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
     pub(crate) fn to_raw_with_blinding_rng<R: Rng>(self, rng: &mut R) -> RawRgbAssignments {
         self.0
             .into_iter()
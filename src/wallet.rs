@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use amplify::ByteArray;
+use commit_verify::{CommitId, Conceal};
+use rgbstd::containers::{AnchorSet, Fascia, ValidTransfer};
+use rgbstd::persistence::{
+    IndexProvider, MemIndex, MemStash, MemState, StashProvider,
+    StateProvider, Stock,
+};
+use rgbstd::validation::{ResolveWitness, Status};
+use rgbstd::XWitnessId;
+
+use crate::api::{filter_rgb_outpoints, rgb_balance, rgb_coin_select, rgb_commit, rgb_compose};
+use crate::detail::{check_contract_network, check_genesis_network, PartialFascia};
+use crate::error::WalletError;
+use crate::resolvers::{ChainResolver, FasciaResolver};
+use crate::types::{
+    Beneficiary, ContractId, Network, Outpoint, RawOutpoint, RgbAssignments, ToRaw, Txid,
+};
+
+// Inputs and change of a send which wasn't consumed yet, keyed by its commitment.
+struct PendingSend {
+    inputs: Vec<Outpoint>,
+    change_seal: Option<Beneficiary>,
+}
+
+/// Watch-only wallet keeping track of the outpoints owning RGB state.
+///
+/// Owned outpoints are added when the wallet receives a transfer or consumes
+/// the fascia of its own send, and removed once they are spent by a send.
+///
+/// Inputs of a send are locked until its fascia is consumed or the send is
+/// cancelled, so they are neither counted nor selected by the next sends.
+///
+/// Owned outpoints and sends waiting for their fascia are kept in memory only,
+/// the stock doesn't persist them. After a restart callers must re-register
+/// the owned outpoints with `with_utxos` or `add_utxo`, and consume the fascia
+/// of a send made before it with the change outpoint already registered.
+pub struct RgbWallet<R, S = MemStash, H = MemState, P = MemIndex>
+where
+    R: ResolveWitness,
+    S: StashProvider,
+    H: StateProvider,
+    P: IndexProvider,
+{
+    stock: Stock<S, H, P>,
+    utxos: BTreeSet<Outpoint>,
+    resolver: R,
+    network: Network,
+    pending: HashMap<[u8; 32], PendingSend>,
+}
+
+impl<R, S, H, P> RgbWallet<R, S, H, P>
+where
+    R: ResolveWitness,
+    S: StashProvider,
+    H: StateProvider,
+    P: IndexProvider,
+{
+    pub fn new(stock: Stock<S, H, P>, resolver: R, network: Network) -> Self {
+        Self::with_utxos(stock, resolver, network, [])
+    }
+
+    pub fn with_utxos(
+        stock: Stock<S, H, P>,
+        resolver: R,
+        network: Network,
+        utxos: impl IntoIterator<Item = Outpoint>,
+    ) -> Self {
+        Self {
+            stock,
+            utxos: utxos.into_iter().collect(),
+            resolver,
+            network,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn stock(&self) -> &Stock<S, H, P> {
+        &self.stock
+    }
+
+    pub fn stock_mut(&mut self) -> &mut Stock<S, H, P> {
+        &mut self.stock
+    }
+
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    pub fn resolver_mut(&mut self) -> &mut R {
+        &mut self.resolver
+    }
+
+    pub fn utxos(&self) -> impl Iterator<Item = &Outpoint> {
+        self.utxos.iter()
+    }
+
+    pub fn add_utxo(&mut self, outpoint: Outpoint) -> bool {
+        self.utxos.insert(outpoint)
+    }
+
+    pub fn remove_utxo(&mut self, outpoint: &Outpoint) -> bool {
+        self.utxos.remove(outpoint)
+    }
+
+    pub fn into_stock(self) -> Stock<S, H, P> {
+        self.stock
+    }
+
+    /// Balance of the owned outpoints, zero if the contract is unknown or
+    /// wasn't issued for the wallet network.
+    pub fn balance(&self, contract_id: ContractId) -> u64 {
+        if self.stock.contract_state(contract_id.to_raw()).is_err() {
+            return 0;
+        }
+        let utxos = self.utxos.iter().copied().collect::<Vec<_>>();
+        rgb_balance(&self.stock, contract_id, &utxos, self.network).unwrap_or_default()
+    }
+
+    // Owned outpoints which aren't inputs of a pending send.
+    fn spendable_utxos(&self) -> Vec<Outpoint> {
+        let locked = self
+            .pending
+            .values()
+            .flat_map(|pending| &pending.inputs)
+            .collect::<BTreeSet<_>>();
+        self.utxos
+            .iter()
+            .filter(|outpoint| !locked.contains(outpoint))
+            .copied()
+            .collect()
+    }
+
+    /// Selects the owned outpoints paying for `rgb_assignments` and colors them.
+    ///
+    /// Returns the inputs in the order they must appear in the witness tx
+    /// (more inputs may be appended after them), the commitment to put in the
+    /// opret output and the fascia to complete once the tx is built.
+    pub fn send(
+        &mut self,
+        rgb_assignments: RgbAssignments,
+        change_seal: Option<Beneficiary>,
+    ) -> Result<(Vec<Outpoint>, [u8; 32], PartialFascia), WalletError> {
+        let utxos = self.spendable_utxos();
+        for (&contract_id, recipients) in &rgb_assignments.0 {
+            if self.stock.contract_state(contract_id.to_raw()).is_err() {
+                return Err(WalletError::UnknownContract(contract_id));
+            }
+            check_contract_network(&self.stock, contract_id.to_raw(), self.network)?;
+            let needed = recipients.values().sum::<u64>();
+            let available = rgb_balance(&self.stock, contract_id, &utxos, self.network)?;
+            if available < needed {
+                return Err(WalletError::InsufficientFunds { contract_id, needed, available });
+            }
+        }
+
+        let inputs = rgb_coin_select(&self.stock, &utxos, &rgb_assignments);
+        let ti_list = rgb_compose(
+            &self.stock,
+            inputs.clone(),
+            rgb_assignments,
+            change_seal.clone(),
+            self.network,
+        )?;
+        let (commitment, partial_fascia) = rgb_commit(&inputs, ti_list);
+
+        self.pending.insert(
+            commitment,
+            PendingSend {
+                inputs: inputs.clone(),
+                change_seal,
+            },
+        );
+
+        Ok((inputs, commitment, partial_fascia))
+    }
+
+    /// Drops a send whose witness tx won't be broadcast, unlocking its inputs.
+    pub fn cancel_send(&mut self, commitment: &[u8; 32]) -> bool {
+        self.pending.remove(commitment).is_some()
+    }
+
+    /// Consumes the fascia of a witness tx spending owned outpoints.
+    ///
+    /// The spent outpoints are dropped and, for a fascia produced by `send`,
+    /// the change output is added to the owned ones.
+    ///
+    /// The witness is resolved from the fascia first, then with the wallet
+    /// resolver.
+    pub fn consume_fascia(&mut self, fascia: Fascia) -> Result<(), WalletError> {
+        let txid = match fascia.witness_id() {
+            XWitnessId::Bitcoin(txid) => Txid::from(txid),
+            XWitnessId::Liquid(_) => {
+                return Err(WalletError::Unsupported("liquid witness".to_string()))
+            }
+        };
+        let commitment = match fascia.anchor {
+            AnchorSet::Opret(ref anchor) => anchor.mpc_proof.commit_id().to_byte_array(),
+            _ => return Err(WalletError::Unsupported("non-opret commitment".to_string())),
+        };
+        let spent = fascia
+            .witness
+            .as_reduced_unsafe()
+            .tx()
+            .map(|tx| {
+                tx.inputs
+                    .iter()
+                    .map(|txin| Outpoint::new(txin.prev_output.txid, txin.prev_output.vout.into_u32()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let resolver = ChainResolver::new()
+            .with_resolver(FasciaResolver::new(&fascia))
+            .with_resolver(&self.resolver);
+        self.stock
+            .consume_fascia(fascia, resolver)
+            .map_err(|e| WalletError::Stock(e.to_string()))?;
+
+        for outpoint in &spent {
+            self.utxos.remove(outpoint);
+        }
+        if let Some(pending) = self.pending.remove(&commitment) {
+            for outpoint in &pending.inputs {
+                self.utxos.remove(outpoint);
+            }
+            let change = match pending.change_seal {
+                Some(Beneficiary::WitnessVout(vout)) => Some(Outpoint::new(txid, vout)),
+                Some(Beneficiary::Outpoint(outpoint)) => Some(outpoint),
+                Some(Beneficiary::SecretSeal(_)) | None => None,
+            };
+            if let Some(change) = change {
+                self.utxos
+                    .extend(filter_rgb_outpoints(&self.stock, &[change])?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accepts a transfer and takes ownership of the outputs it assigns to
+    /// the secret seals of the stock.
+    ///
+    /// Transfers of contracts issued for another network are refused.
+    pub fn accept_transfer(&mut self, transfer: ValidTransfer) -> Result<Status, WalletError> {
+        check_genesis_network(&transfer.genesis, self.network)?;
+
+        let witnesses = transfer
+            .bundles
+            .iter()
+            .flat_map(|bw| {
+                let witness_id = bw.witness_id();
+                bw.anchored_bundles()
+                    .map(move |(_, bundle)| (bundle.bundle_id(), witness_id))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let terminals = transfer
+            .terminals
+            .iter()
+            .filter_map(|(bundle_id, seal)| Some((*seal, *witnesses.get(bundle_id)?)))
+            .collect::<Vec<_>>();
+
+        let status = self
+            .stock
+            .accept_transfer(transfer, &self.resolver)
+            .map_err(|e| WalletError::Stock(e.to_string()))?;
+
+        let received = self
+            .stock
+            .as_stash_provider()
+            .secret_seals()
+            .map_err(|e| WalletError::Stock(e.to_string()))?
+            .filter_map(|seal| {
+                let (_, witness_id) = terminals.iter().find(|(s, _)| *s == seal.conceal())?;
+                let output = seal.try_to_output_seal(*witness_id).ok()?;
+                Some(Outpoint::from(RawOutpoint::from(output.map(|o| o.to_outpoint()))))
+            })
+            .collect::<Vec<_>>();
+        self.utxos
+            .extend(filter_rgb_outpoints(&self.stock, &received)?);
+
+        Ok(status)
+    }
+}